@@ -0,0 +1,28 @@
+extern crate github_app;
+
+use std::env;
+use std::path::PathBuf;
+
+#[tokio::main]
+async fn main() -> Result<(), github_app::Error> {
+    let mut args = env::args();
+    let _ = args.next();
+    if let (Some(path), Some(installation_id)) = (args.next(), args.next()) {
+        list_repos(&path, installation_id.parse::<usize>()?).await?;
+    } else {
+        println!("Usage: async_list_repos path/to/private_key.der installation_id");
+    }
+    Ok(())
+}
+
+async fn list_repos(path: &str, installation_id: usize) -> Result<(), github_app::Error> {
+    let path: PathBuf = path.into();
+    let app = github_app::nonblocking::App::from_private_key_file(&path, "26261")?;
+    let installation = app.installation(installation_id).await?;
+    // Spawned so that the compiler checks the futures are Send
+    let repos = tokio::spawn(async move { installation.repos().await })
+        .await
+        .expect("task panicked")?;
+    println!("{:?}", repos);
+    Ok(())
+}
@@ -4,7 +4,6 @@ use std::env;
 use std::path::PathBuf;
 
 use log::Level;
-use simple_logger;
 
 fn main() -> Result<(), github_app::Error> {
     let mut args = env::args();
@@ -41,8 +41,11 @@ fn fetch_repos(
     repos: Vec<github_app::Repo>,
     pull_requests: &mut Vec<github_app::PullRequest>,
 ) {
-    for repo in repos {
-        match repo.pull_requests(&installation, Some(github_app::PullRequestState::Open)) {
+    let results = installation.concurrently(repos, 8, |installation, repo| {
+        repo.pull_requests(installation, Some(github_app::PullRequestState::Open))
+    });
+    for result in results {
+        match result {
             Ok(mut pulls) => {
                 pull_requests.append(&mut pulls);
             }
@@ -26,20 +26,18 @@ fn mark_pr_pending(
     let installation = app.installation(installation_id)?;
     // let repos = installation.repos()?;
     let pr = installation.pull_request(pull_request_id)?;
-    let context = "GithubApp Test".into();
-    if let Some(state) = pr.last_status_for_context(&installation, &context)? {
+    let context = "GithubApp Test";
+    if let Some(state) = pr.last_status_for_context(&installation, context)? {
         if state.state == github_app::pull_request::State::Success {
             println!("PR already has the desired state!");
             return Ok(())
         }
     }
     // println!("Pull request: {:#?}", pr);
-    let status = github_app::pull_request::Status {
-        state: github_app::pull_request::State::Success,
-        target_url: "https://example.com".into(),
-        description: "This is a test".into(),
-        context: context,
-    };
+    let status =
+        github_app::pull_request::Status::new(github_app::pull_request::State::Success, context)
+            .target_url("https://example.com")
+            .description("This is a test");
     println!("Updating PR to the desired state!");
     pr.set_status(&installation, &status)?;
     Ok(())
@@ -27,20 +27,18 @@ fn mark_pr_pending(
     // let repos = installation.repos()?;
     let pr = installation.pull_request(pull_request_id)?;
     // println!("Pull request: {:#?}", pr);
-    let context = "GithubApp Test".into();
-    if let Some(state) = pr.last_status_for_context(&installation, &context)? {
+    let context = "GithubApp Test";
+    if let Some(state) = pr.last_status_for_context(&installation, context)? {
         if state.state == github_app::pull_request::State::Pending {
             println!("PR already has the desired state!");
             return Ok(())
         }
     }
     // println!("Pull request: {:#?}", pr);
-    let status = github_app::pull_request::Status {
-        state: github_app::pull_request::State::Pending,
-        target_url: "https://example.com".into(),
-        description: "This is a test".into(),
-        context: context,
-    };
+    let status =
+        github_app::pull_request::Status::new(github_app::pull_request::State::Pending, context)
+            .target_url("https://example.com")
+            .description("This is a test");
     println!("Updating PR to the desired state!");
     pr.set_status(&installation, &status)?;
     Ok(())
@@ -0,0 +1,35 @@
+extern crate github_app;
+
+use std::env;
+use std::fs;
+
+fn main() -> Result<(), github_app::Error> {
+    let mut args = env::args();
+    let _ = args.next();
+    if let (Some(secret), Some(signature), Some(event), Some(payload_path)) =
+        (args.next(), args.next(), args.next(), args.next())
+    {
+        verify_webhook(&secret, &signature, &event, &payload_path)?
+    } else {
+        println!("Usage: verify_webhook $SECRET $SIGNATURE $EVENT path/to/payload.json");
+    }
+    Ok(())
+}
+
+fn verify_webhook(
+    secret: &str,
+    signature: &str,
+    event: &str,
+    payload_path: &str,
+) -> Result<(), github_app::Error> {
+    let body = fs::read(payload_path)?;
+    let webhook = github_app::Webhook::new(secret);
+    let headers = vec![
+        ("X-Hub-Signature-256", signature),
+        ("X-GitHub-Event", event),
+        ("X-GitHub-Delivery", "00000000-0000-0000-0000-000000000000"),
+    ];
+    let delivery = webhook.parse(headers, &body)?;
+    println!("{:#?}", delivery);
+    Ok(())
+}
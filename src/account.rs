@@ -28,7 +28,7 @@ pub struct Team {
     pub url: String,
     pub name: String,
     pub slug: String,
-    pub description: String,
+    pub description: Option<String>,
     pub privacy: String,
     pub permission: String,
     pub members_url: String,
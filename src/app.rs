@@ -1,117 +1,432 @@
 use core::fmt;
 use core::ops::Deref;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
 
 use chrono::prelude::*;
-use curl::easy::{Easy, List};
 use failure::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use crate::Installation;
+use crate::config::ConfigCache;
+use crate::rate_limit::{self, RateLimits, CORE_RESOURCE};
+use crate::transport::{CurlTransport, Method, Request, Response, Transport};
+use crate::GithubError;
 use crate::JsonWebToken;
+use crate::Paginated;
 use crate::PullRequest;
+use crate::{Installation, InstallationRepository};
+use crate::{RateLimit, RateLimitPolicy};
 use crate::{Repo, RepoResult};
 
+const DEFAULT_API_URL: &str = "https://api.github.com";
+const DEFAULT_UPLOADS_URL: &str = "https://uploads.github.com";
+const DEFAULT_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+const DEFAULT_PER_PAGE: usize = 100;
+const DEFAULT_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// Installation tokens last an hour, so a longer margin would have every
+/// request mint a new one.
+const MAX_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(30 * 60);
+
 #[derive(Clone, Debug)]
 pub struct App {
+    pub(crate) json_web_token: JsonWebToken,
+    api_url: String,
+    uploads_url: String,
+    graphql_url: String,
+    pub(crate) per_page: usize,
+    pub(crate) rate_limit_policy: RateLimitPolicy,
+    pub(crate) rate_limit: Arc<RwLock<RateLimits>>,
+    pub(crate) token_refresh_margin: Duration,
+    transport: Arc<dyn Transport>,
+}
+
+/// Builds an `App` that talks to something other than github.com, such as a
+/// GitHub Enterprise Server instance or a local mock server.
+#[derive(Clone, Debug)]
+pub struct AppBuilder {
     json_web_token: JsonWebToken,
+    api_url: String,
+    uploads_url: String,
+    graphql_url: String,
+    per_page: usize,
+    rate_limit_policy: RateLimitPolicy,
+    token_refresh_margin: Duration,
+    transport: Arc<dyn Transport>,
+}
+
+impl AppBuilder {
+    pub fn new(json_web_token: JsonWebToken) -> AppBuilder {
+        AppBuilder {
+            json_web_token,
+            api_url: DEFAULT_API_URL.into(),
+            uploads_url: DEFAULT_UPLOADS_URL.into(),
+            graphql_url: DEFAULT_GRAPHQL_URL.into(),
+            per_page: DEFAULT_PER_PAGE,
+            rate_limit_policy: RateLimitPolicy::default(),
+            token_refresh_margin: DEFAULT_TOKEN_REFRESH_MARGIN,
+            transport: Arc::new(CurlTransport::new()),
+        }
+    }
+
+    /// Points the REST, uploads and GraphQL URLs at a GitHub Enterprise Server
+    /// host, e.g. `https://github.example.com`.
+    pub fn enterprise_url<T: AsRef<str>>(self, base_url: T) -> AppBuilder {
+        let base_url = base_url.as_ref().trim_end_matches('/');
+        AppBuilder {
+            api_url: format!("{}/api/v3", base_url),
+            uploads_url: format!("{}/api/uploads", base_url),
+            graphql_url: format!("{}/api/graphql", base_url),
+            ..self
+        }
+    }
+
+    pub fn api_url<T: Into<String>>(mut self, api_url: T) -> AppBuilder {
+        self.api_url = api_url.into();
+        self
+    }
+
+    pub fn uploads_url<T: Into<String>>(mut self, uploads_url: T) -> AppBuilder {
+        self.uploads_url = uploads_url.into();
+        self
+    }
+
+    pub fn graphql_url<T: Into<String>>(mut self, graphql_url: T) -> AppBuilder {
+        self.graphql_url = graphql_url.into();
+        self
+    }
+
+    /// Default page size for list endpoints; GitHub caps this at 100.
+    pub fn per_page(mut self, per_page: usize) -> AppBuilder {
+        self.per_page = per_page;
+        self
+    }
+
+    pub fn rate_limit_policy(mut self, rate_limit_policy: RateLimitPolicy) -> AppBuilder {
+        self.rate_limit_policy = rate_limit_policy;
+        self
+    }
+
+    /// How long before an installation token expires to replace it, so that
+    /// a request never goes out with a token that's about to lapse. Defaults
+    /// to five minutes, and is capped at 30 minutes, half a token's lifetime.
+    pub fn token_refresh_margin(mut self, margin: Duration) -> AppBuilder {
+        if margin > MAX_TOKEN_REFRESH_MARGIN {
+            warn!(
+                "Token refresh margin of {:?} is too long, using {:?}",
+                margin, MAX_TOKEN_REFRESH_MARGIN
+            );
+        }
+        self.token_refresh_margin = margin.min(MAX_TOKEN_REFRESH_MARGIN);
+        self
+    }
+
+    /// Replaces the default curl-based HTTP transport. The async API always
+    /// uses reqwest.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> AppBuilder {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    pub fn build(self) -> App {
+        App {
+            json_web_token: self.json_web_token,
+            api_url: self.api_url.trim_end_matches('/').into(),
+            uploads_url: self.uploads_url.trim_end_matches('/').into(),
+            graphql_url: self.graphql_url,
+            per_page: self.per_page,
+            rate_limit_policy: self.rate_limit_policy,
+            rate_limit: Arc::new(RwLock::new(RateLimits::default())),
+            token_refresh_margin: self.token_refresh_margin,
+            transport: self.transport,
+        }
+    }
 }
 
 impl App {
     pub fn new<T: Into<String>>(private_key: Vec<u8>, app_id: T) -> Result<App, Error> {
-        Ok(App {
-            json_web_token: JsonWebToken::new(private_key, app_id)?,
-        })
+        Ok(App::builder(JsonWebToken::new(private_key, app_id)?).build())
     }
 
     pub fn from_private_key_file<T: Into<String>>(path: &PathBuf, app_id: T) -> Result<App, Error> {
-        Ok(App {
-            json_web_token: JsonWebToken::from_private_key_file(path, app_id)?,
-        })
+        Ok(App::builder(JsonWebToken::from_private_key_file(path, app_id)?).build())
+    }
+
+    pub fn builder(json_web_token: JsonWebToken) -> AppBuilder {
+        AppBuilder::new(json_web_token)
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn uploads_url(&self) -> &str {
+        &self.uploads_url
+    }
+
+    pub fn graphql_url(&self) -> &str {
+        &self.graphql_url
+    }
+
+    /// Joins `path` onto the configured REST API base URL.
+    pub(crate) fn api<T: AsRef<str>>(&self, path: T) -> String {
+        format!("{}/{}", self.api_url, path.as_ref().trim_start_matches('/'))
+    }
+
+    pub fn per_page(&self) -> usize {
+        self.per_page
+    }
+
+    /// The core REST rate limit for requests authenticated as the app itself.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit_for(CORE_RESOURCE)
+    }
+
+    /// The rate limit for another resource, e.g. `search` or `graphql`.
+    pub fn rate_limit_for<T: AsRef<str>>(&self, resource: T) -> Option<RateLimit> {
+        self.rate_limit
+            .read()
+            .unwrap()
+            .get(resource.as_ref())
+            .cloned()
+    }
+
+    pub(crate) fn rate_limit_resource(&self, url: &str) -> &'static str {
+        rate_limit::resource_for(&self.api_url, &self.graphql_url, url)
     }
 
     pub fn installations(&self) -> Result<Vec<AppInstallation>, Error> {
-        let token: String = self.json_web_token.token()?;
-        let data = get_with_token("https://api.github.com/app/installations", token)?;
-        let installations: Vec<Installation> = serde_json::from_slice(&data)?;
-        Ok(installations
-            .into_iter()
-            .map(|ins| AppInstallation {
-                app: self.clone(),
-                installation_token: RwLock::new(None),
-                installation: ins,
-            })
-            .collect())
+        self.iter_installations().collect()
+    }
+
+    pub fn iter_installations(&self) -> Paginated<'_, AppInstallation> {
+        Paginated::new(
+            self.api("app/installations"),
+            self.per_page,
+            move |url| self.jwt_request(Method::Get, url, None),
+            move |body| {
+                let installations: Vec<Installation> = serde_json::from_slice(body)?;
+                Ok(installations
+                    .into_iter()
+                    .map(|ins| AppInstallation::new(self.clone(), ins))
+                    .collect())
+            },
+        )
     }
 
     pub fn installation(&self, installation_id: usize) -> Result<AppInstallation, Error> {
+        let data = self.jwt_request(
+            Method::Get,
+            self.api(format!("app/installations/{}", installation_id)),
+            None,
+        )?;
+        let installation: Installation = serde_json::from_slice(&data.body)?;
+        Ok(AppInstallation::new(self.clone(), installation))
+    }
+
+    fn jwt_request<T: AsRef<str>>(
+        &self,
+        method: Method,
+        url: T,
+        body: Option<&[u8]>,
+    ) -> Result<Response, Error> {
         let token: String = self.json_web_token.token()?;
-        let data = get_with_token(
-            format!(
-                "https://api.github.com/app/installations/{}",
-                installation_id
+        self.execute(
+            &self.rate_limit,
+            method,
+            url.as_ref(),
+            format!("Bearer {}", token),
+            vec![],
+            body,
+        )
+    }
+
+    /// Sends a request through the transport, recording the rate limit headers into `rate_limit`
+    /// and retrying according to the configured `RateLimitPolicy`.
+    fn execute(
+        &self,
+        rate_limit: &RwLock<RateLimits>,
+        method: Method,
+        url: &str,
+        authorization: String,
+        headers: Vec<(String, String)>,
+        body: Option<&[u8]>,
+    ) -> Result<Response, Error> {
+        let mut defaults = vec![
+            ("Authorization".to_string(), authorization),
+            ("User-Agent".into(), crate::USER_AGENT.into()),
+            (
+                "Accept".into(),
+                "application/vnd.github.machine-man-preview+json".into(),
             ),
-            token,
-        )?;
-        let installation: Installation = serde_json::from_slice(&data)?;
-        Ok(AppInstallation {
-            app: self.clone(),
-            installation_token: RwLock::new(None),
-            installation: installation,
-        })
+        ];
+        if body.is_some() {
+            defaults.push(("Content-Type".into(), "application/json".into()));
+        }
+        defaults.retain(|(key, _)| !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)));
+        defaults.extend(headers);
+        let request = Request {
+            method,
+            url: url.to_string(),
+            headers: defaults,
+            body: body.map(|body| body.to_vec()),
+        };
+        let resource = self.rate_limit_resource(url);
+        let mut attempt = 0;
+        loop {
+            let delay = self
+                .rate_limit_policy
+                .delay_before(rate_limit.read().unwrap().get(resource));
+            if let Some(delay) = delay {
+                info!("Rate limit exhausted, sleeping {:?} until it resets", delay);
+                thread::sleep(delay);
+            }
+            let response = self.transport.send(&request)?;
+            if let Some(limit) = RateLimit::from_response(&response) {
+                trace!("Rate limit: {:?}", limit);
+                rate_limit.write().unwrap().record(limit);
+            }
+            match GithubError::check(response) {
+                Ok(response) => return Ok(response),
+                Err(e) => match self.rate_limit_policy.retry_delay(&e, attempt) {
+                    Some(delay) => {
+                        info!("Rate limited ({}), retrying in {:?}", e, delay);
+                        thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    None => return Err(e.into()),
+                },
+            }
+        }
     }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct InstallationToken {
     pub token: String,
-    expires_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// What the token may do, e.g. `contents` => `read`.
+    #[serde(default)]
+    pub permissions: BTreeMap<String, String>,
+    /// `all` or `selected`.
+    pub repository_selection: Option<String>,
+    /// The repositories the token was restricted to, if any.
+    pub repositories: Option<Vec<InstallationRepository>>,
+}
+
+/// Narrows an installation token down to some of the installation's
+/// repositories and permissions. Anything left empty is not restricted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TokenRequest {
+    /// Repository names, without the owner.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repositories: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repository_ids: Vec<usize>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub permissions: BTreeMap<String, String>,
+}
+
+impl InstallationToken {
+    /// Whether the token is still good for at least `margin`.
+    pub(crate) fn is_fresh(&self, margin: Duration) -> bool {
+        let margin =
+            chrono::Duration::from_std(margin).unwrap_or_else(|_| chrono::Duration::zero());
+        self.expires_at - margin > Utc::now()
+    }
+}
+
+impl TokenRequest {
+    pub fn new() -> TokenRequest {
+        TokenRequest::default()
+    }
+
+    pub fn repository<T: Into<String>>(mut self, name: T) -> Self {
+        self.repositories.push(name.into());
+        self
+    }
+
+    pub fn repository_id(mut self, id: usize) -> Self {
+        self.repository_ids.push(id);
+        self
+    }
+
+    /// `level` is usually `read` or `write`, and can't exceed what the
+    /// installation was granted.
+    pub fn permission<T1: Into<String>, T2: Into<String>>(mut self, name: T1, level: T2) -> Self {
+        self.permissions.insert(name.into(), level.into());
+        self
+    }
 }
 
 pub struct AppInstallation {
     app: App,
     installation_token: RwLock<Option<InstallationToken>>,
+    // Held while minting a token so concurrent requests don't each mint one
+    token_refresh: Mutex<()>,
+    token_request: Option<TokenRequest>,
+    rate_limit: RwLock<RateLimits>,
+    pub(crate) config_cache: Mutex<ConfigCache>,
     installation: Installation,
 }
 
 impl AppInstallation {
+    pub(crate) fn new(app: App, installation: Installation) -> AppInstallation {
+        AppInstallation {
+            app,
+            installation_token: RwLock::new(None),
+            token_refresh: Mutex::new(()),
+            token_request: None,
+            rate_limit: RwLock::new(RateLimits::default()),
+            config_cache: Mutex::new(ConfigCache::default()),
+            installation,
+        }
+    }
+
     fn installation_token(&self) -> Result<String, Error> {
-        // this annoying jump is to ensure we release the read lock before we try to take the write lock
-        let t = {
-            match *self.installation_token.read().unwrap() {
-                Some(ref t) => Some(t.clone()),
-                None => None,
-            }
-        };
         debug!("Checking if App Installation token is available");
-        let token = match t {
-            Some(token) => {
-                debug!("Token expires at: {}", token.expires_at);
-                if token.expires_at >= Utc::now() {
-                    debug!("Token expired!");
-                    self.refresh_token()?
-                } else {
-                    token
-                }
+        if let Some(token) = self.fresh_token() {
+            return Ok(token);
+        }
+        let _refreshing = self.token_refresh.lock().unwrap();
+        // Another thread may have refreshed the token while we waited
+        if let Some(token) = self.fresh_token() {
+            return Ok(token);
+        }
+        Ok(self.refresh_token()?.token)
+    }
+
+    fn fresh_token(&self) -> Option<String> {
+        match *self.installation_token.read().unwrap() {
+            Some(ref token) if token.is_fresh(self.app.token_refresh_margin) => {
+                Some(token.token.clone())
+            }
+            Some(ref token) => {
+                debug!("Token expires at {}, refreshing it", token.expires_at);
+                None
             }
             None => {
                 debug!("No token present, getting one!");
-                self.refresh_token()?
+                None
             }
-        };
-        Ok(token.token)
+        }
     }
 
     fn refresh_token(&self) -> Result<InstallationToken, Error> {
         info!("Renewing App Installation token for {}", self.id);
-        let token: String = self.app.json_web_token.token()?;
-        let data = post(
-            &self.access_tokens_url,
-            vec![format!("Authorization: Bearer {}", token)],
-            None,
-        )?;
-        let token: InstallationToken = serde_json::from_slice(&data)?;
+        let token = match self.token_request {
+            Some(ref request) => self.create_token(request)?,
+            None => {
+                let data =
+                    self.app
+                        .jwt_request(Method::Post, self.access_tokens_api_url(), None)?;
+                serde_json::from_slice(&data.body)?
+            }
+        };
         trace!("Updated App Installation token for {}", self.id);
         let mut t = self.installation_token.write().unwrap();
         *t = Some(token.clone());
@@ -119,10 +434,126 @@ impl AppInstallation {
         Ok(token)
     }
 
+    /// Mints a new token restricted by `request`, separate from the one this
+    /// `AppInstallation` uses for its own requests. Handy for handing a
+    /// least-privilege token to another process.
+    pub fn create_token(&self, request: &TokenRequest) -> Result<InstallationToken, Error> {
+        let body = serde_json::to_vec(request)?;
+        let data = self
+            .app
+            .jwt_request(Method::Post, self.access_tokens_api_url(), Some(&body))?;
+        Ok(serde_json::from_slice(&data.body)?)
+    }
+
+    /// A copy of this installation whose requests all use tokens restricted
+    /// by `request`.
+    pub fn scoped(&self, request: TokenRequest) -> AppInstallation {
+        AppInstallation {
+            token_request: Some(request),
+            ..AppInstallation::new(self.app.clone(), self.installation.clone())
+        }
+    }
+
+    /// The token this installation is currently using, including what it
+    /// was actually granted. `None` until the first request mints one.
+    pub fn current_token(&self) -> Option<InstallationToken> {
+        self.installation_token.read().unwrap().clone()
+    }
+
+    /// Revokes the token this installation is using, if any. A new one is
+    /// minted for the next request.
+    pub fn revoke(&self) -> Result<(), Error> {
+        let _refreshing = self.token_refresh.lock().unwrap();
+        let token = self.installation_token.write().unwrap().take();
+        match token {
+            Some(ref token) if token.expires_at > Utc::now() => {
+                info!("Revoking App Installation token for {}", self.id);
+                self.app.execute(
+                    &self.rate_limit,
+                    Method::Delete,
+                    &self.api("installation/token"),
+                    format!("token {}", token.token),
+                    vec![],
+                    None,
+                )?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn access_tokens_api_url(&self) -> String {
+        self.api(format!("app/installations/{}/access_tokens", self.id))
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    /// Joins `path` onto the configured REST API base URL.
+    pub fn api<T: AsRef<str>>(&self, path: T) -> String {
+        self.app.api(path)
+    }
+
+    /// The core REST rate limit for requests made with this installation's
+    /// token.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit_for(CORE_RESOURCE)
+    }
+
+    /// The rate limit for another resource, e.g. `search` or `graphql`.
+    pub fn rate_limit_for<T: AsRef<str>>(&self, resource: T) -> Option<RateLimit> {
+        self.rate_limit
+            .read()
+            .unwrap()
+            .get(resource.as_ref())
+            .cloned()
+    }
+
     pub fn repos(&self) -> Result<Vec<Repo>, Error> {
-        let data = self.get(&self.repositories_url)?;
-        let result: RepoResult = serde_json::from_slice(&data)?;
-        Ok(result.repositories)
+        self.iter_repos().collect()
+    }
+
+    pub fn iter_repos(&self) -> Paginated<'_, Repo> {
+        self.paginate_with(self.api("installation/repositories"), |body| {
+            let result: RepoResult = serde_json::from_slice(body)?;
+            Ok(result.repositories)
+        })
+    }
+
+    /// Calls `f` for each of `items` on up to `concurrency` threads, returning
+    /// the results in the same order as `items`. Requests share this
+    /// installation's token and the app's pooled connections.
+    pub fn concurrently<I, T, R, F>(&self, items: I, concurrency: usize, f: F) -> Vec<R>
+    where
+        I: IntoIterator<Item = T>,
+        T: Send,
+        R: Send,
+        F: Fn(&AppInstallation, T) -> R + Sync,
+    {
+        let items: Vec<T> = items.into_iter().collect();
+        let count = items.len();
+        let queue = Mutex::new(items.into_iter().enumerate());
+        let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<R>>>());
+        thread::scope(|scope| {
+            for _ in 0..concurrency.max(1).min(count) {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap().next();
+                    let (idx, item) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let result = f(self, item);
+                    results.lock().unwrap()[idx] = Some(result);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every item is processed before the scope ends"))
+            .collect()
     }
 
     /// pull_request_path should be of the form: :owner/:repo/pulls/:number
@@ -130,36 +561,162 @@ impl AppInstallation {
     where
         T: Into<Cow<'a, str>>,
     {
-        let installation_token = self.installation_token()?;
-        let data = get_with_token(
-            format!("https://api.github.com/repos/{}", pull_request_path.into()),
-            &installation_token,
-        )?;
-        Ok(serde_json::from_slice(&data)?)
+        let data = self.get_response(self.api(format!("repos/{}", pull_request_path.into())))?;
+        Ok(serde_json::from_slice(&data.body)?)
     }
 
-    pub(crate) fn get<T1: AsRef<str>>(&self, url: T1) -> Result<Vec<u8>, Error> {
-        let installation_token = self.installation_token()?;
-        get(
+    /// Lazily walks a list endpoint that returns a JSON array.
+    pub fn paginate<T, U>(&self, url: U) -> Paginated<'_, T>
+    where
+        T: DeserializeOwned,
+        U: Into<String>,
+    {
+        self.paginate_with(url, |body| Ok(serde_json::from_slice(body)?))
+    }
+
+    pub(crate) fn paginate_with<'a, T, U, F>(&'a self, url: U, parse: F) -> Paginated<'a, T>
+    where
+        U: Into<String>,
+        F: Fn(&[u8]) -> Result<Vec<T>, Error> + 'a,
+    {
+        Paginated::new(
             url,
-            vec![format!("Authorization: token {}", installation_token)],
+            self.app.per_page,
+            move |url| self.get_response(url),
+            parse,
         )
     }
 
+    pub(crate) fn get_response<T1: AsRef<str>>(&self, url: T1) -> Result<Response, Error> {
+        self.request(Method::Get, url, None)
+    }
+
     pub(crate) fn post<T1: AsRef<str>>(
         &self,
         url: T1,
         body: Option<&[u8]>,
     ) -> Result<Vec<u8>, Error> {
+        Ok(self.request(Method::Post, url, body)?.body)
+    }
+
+    pub fn get_json<T1, R>(&self, url: T1) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        R: DeserializeOwned,
+    {
+        parse_json(&self.get_response(url)?.body)
+    }
+
+    pub fn post_json<T1, B, R>(&self, url: T1, body: &B) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::Post, url, body)
+    }
+
+    pub fn put_json<T1, B, R>(&self, url: T1, body: &B) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::Put, url, body)
+    }
+
+    pub fn patch_json<T1, B, R>(&self, url: T1, body: &B) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::Patch, url, body)
+    }
+
+    pub fn delete<T1: AsRef<str>>(&self, url: T1) -> Result<(), Error> {
+        self.request(Method::Delete, url, None)?;
+        Ok(())
+    }
+
+    /// For the handful of DELETE endpoints that take a JSON body.
+    pub fn delete_json<T1, B, R>(&self, url: T1, body: &B) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::Delete, url, body)
+    }
+
+    /// Sends an arbitrary request authenticated as this installation, for
+    /// endpoints this crate doesn't model. `url` may be a full URL or a path
+    /// relative to the API base URL. `headers` are added to, or override,
+    /// the defaults. Error statuses are still turned into `GithubError`s.
+    pub fn raw_request<T1: AsRef<str>>(
+        &self,
+        method: Method,
+        url: T1,
+        headers: Vec<(String, String)>,
+        body: Option<&[u8]>,
+    ) -> Result<Response, Error> {
+        let url = url.as_ref();
+        let url = if url.starts_with("http://") || url.starts_with("https://") {
+            url.to_string()
+        } else {
+            self.api(url)
+        };
+        self.request_with_headers(method, url, headers, body)
+    }
+
+    fn send_json<T1, B, R>(&self, method: Method, url: T1, body: &B) -> Result<R, Error>
+    where
+        T1: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        let json = serde_json::to_vec(body)?;
+        parse_json(&self.request(method, url, Some(&json))?.body)
+    }
+
+    fn request<T1: AsRef<str>>(
+        &self,
+        method: Method,
+        url: T1,
+        body: Option<&[u8]>,
+    ) -> Result<Response, Error> {
+        self.request_with_headers(method, url, vec![], body)
+    }
+
+    pub(crate) fn request_with_headers<T1: AsRef<str>>(
+        &self,
+        method: Method,
+        url: T1,
+        headers: Vec<(String, String)>,
+        body: Option<&[u8]>,
+    ) -> Result<Response, Error> {
         let installation_token = self.installation_token()?;
-        post(
-            url,
-            vec![format!("Authorization: token {}", installation_token)],
+        self.app.execute(
+            &self.rate_limit,
+            method,
+            url.as_ref(),
+            format!("token {}", installation_token),
+            headers,
             body,
         )
     }
 }
 
+/// Deserializes a response body, treating an empty body (e.g. a 204) as
+/// `null` so that callers can ask for `()` or an `Option`.
+pub(crate) fn parse_json<R: DeserializeOwned>(body: &[u8]) -> Result<R, Error> {
+    if body.is_empty() {
+        Ok(serde_json::from_slice(b"null")?)
+    } else {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
 impl Deref for AppInstallation {
     type Target = Installation;
 
@@ -174,65 +731,198 @@ impl fmt::Debug for AppInstallation {
     }
 }
 
-#[derive(Debug)]
-enum Method {
-    Get,
-    Post,
-}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
 
-fn get<T1: AsRef<str>, T2: AsRef<str>>(url: T1, headers: Vec<T2>) -> Result<Vec<u8>, Error> {
-    easy_run(url, headers, Method::Get, None)
-}
+    use super::*;
+    use crate::testing::{self, MockTransport};
 
-fn get_with_token<T1: AsRef<str>, T2: AsRef<str>>(url: T1, token: T2) -> Result<Vec<u8>, Error> {
-    get(
-        url,
-        vec![format!("Authorization: bearer {}", token.as_ref())],
-    )
-}
+    fn token(expires_in: chrono::Duration) -> InstallationToken {
+        InstallationToken {
+            token: "t".into(),
+            expires_at: Utc::now() + expires_in,
+            permissions: BTreeMap::new(),
+            repository_selection: None,
+            repositories: None,
+        }
+    }
 
-fn post<T1: AsRef<str>, T2: AsRef<str>>(
-    url: T1,
-    headers: Vec<T2>,
-    body: Option<&[u8]>,
-) -> Result<Vec<u8>, Error> {
-    easy_run(url, headers, Method::Post, body)
-}
+    /// Mints numbered tokens, and answers anything else with `{}`.
+    fn github(ttl: chrono::Duration) -> MockTransport {
+        testing::github_with_token_ttl(ttl, |_| testing::response(200, &[], "{}"))
+    }
+
+    fn get(installation: &AppInstallation) {
+        let _: serde_json::Value = installation.get_json(installation.api("zen")).unwrap();
+    }
+
+    fn builder() -> AppBuilder {
+        let transport = testing::github(|_| testing::response(200, &[], "{}"));
+        App::builder(testing::app(&transport).json_web_token)
+    }
+
+    #[test]
+    fn builder_defaults_to_github_com() {
+        let app = builder().build();
+        assert_eq!(app.api_url(), "https://api.github.com");
+        assert_eq!(app.uploads_url(), "https://uploads.github.com");
+        assert_eq!(app.graphql_url(), "https://api.github.com/graphql");
+        assert_eq!(app.api("/app"), "https://api.github.com/app");
+    }
+
+    #[test]
+    fn enterprise_url_sets_every_url() {
+        for base_url in &["https://github.example.com", "https://github.example.com/"] {
+            let app = builder().enterprise_url(base_url).build();
+            assert_eq!(app.api_url(), "https://github.example.com/api/v3");
+            assert_eq!(app.uploads_url(), "https://github.example.com/api/uploads");
+            assert_eq!(app.graphql_url(), "https://github.example.com/api/graphql");
+            assert_eq!(
+                app.api("repos/o/r"),
+                "https://github.example.com/api/v3/repos/o/r"
+            );
+        }
+    }
+
+    #[test]
+    fn builder_trims_trailing_slashes() {
+        let app = builder()
+            .api_url("http://localhost:8080/")
+            .uploads_url("http://localhost:8081/")
+            .graphql_url("http://localhost:8080/graphql")
+            .build();
+        assert_eq!(app.api_url(), "http://localhost:8080");
+        assert_eq!(app.uploads_url(), "http://localhost:8081");
+        assert_eq!(app.graphql_url(), "http://localhost:8080/graphql");
+        assert_eq!(app.api("app"), "http://localhost:8080/app");
+    }
+
+    #[test]
+    fn is_fresh() {
+        let margin = DEFAULT_TOKEN_REFRESH_MARGIN;
+        assert!(token(chrono::Duration::hours(1)).is_fresh(margin));
+        // Still valid, but not for long enough
+        assert!(!token(chrono::Duration::minutes(2)).is_fresh(margin));
+        assert!(token(chrono::Duration::minutes(2)).is_fresh(Duration::from_secs(0)));
+        assert!(!token(chrono::Duration::minutes(-1)).is_fresh(margin));
+        assert!(!token(chrono::Duration::minutes(-1)).is_fresh(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn token_refresh_margin_is_capped() {
+        let app = |margin| {
+            builder()
+                .token_refresh_margin(margin)
+                .build()
+                .token_refresh_margin
+        };
+        assert_eq!(
+            app(Duration::from_secs(10 * 60)),
+            Duration::from_secs(10 * 60)
+        );
+        assert_eq!(app(Duration::from_secs(60 * 60)), MAX_TOKEN_REFRESH_MARGIN);
+        assert_eq!(
+            app(Duration::from_secs(24 * 60 * 60)),
+            MAX_TOKEN_REFRESH_MARGIN
+        );
+    }
 
-fn easy_run<T1: AsRef<str>, T2: AsRef<str>>(
-    url: T1,
-    headers: Vec<T2>,
-    method: Method,
-    body: Option<&[u8]>,
-) -> Result<Vec<u8>, Error> {
-    debug!("About to {:?} {}", method, url.as_ref());
-    let dst = Arc::new(RwLock::new(Vec::with_capacity(8192)));
-    let mut easy = Easy::new();
-    let url = url.as_ref();
-    easy.url(url)?;
-
-    let mut list = List::new();
-    for header in headers {
-        list.append(header.as_ref())?;
-    }
-    list.append(&format!("User-Agent: {}", crate::USER_AGENT))?;
-    list.append("Accept: application/vnd.github.machine-man-preview+json")?;
-    easy.http_headers(list)?;
-    let inner_dst = dst.clone();
-    easy.write_function(move |data| {
-        let inner_dst = inner_dst.clone();
-        inner_dst.write().unwrap().extend_from_slice(data);
-        Ok(data.len())
-    })?;
-    match method {
-        Method::Get => easy.get(true)?,
-        Method::Post => easy.post(true)?,
-    }
-    if let Some(data) = body {
-        easy.post_fields_copy(data)?;
-    }
-    easy.perform()?;
-    let data = (*dst.read().unwrap()).to_vec();
-    trace!("Got {:#?}", String::from_utf8_lossy(&data));
-    Ok(data)
+    #[test]
+    fn concurrent_requests_mint_one_token() {
+        let transport = github(chrono::Duration::hours(1));
+        let installation = testing::installation(&transport);
+        installation.concurrently(0..16, 8, |installation, _| get(installation));
+        assert_eq!(testing::mints(&transport), 1);
+        assert_eq!(transport.requests().len(), 17);
+        get(&installation);
+        assert_eq!(testing::mints(&transport), 1);
+    }
+
+    #[test]
+    fn expiring_tokens_are_replaced() {
+        let transport = github(chrono::Duration::minutes(2));
+        let installation = testing::installation(&transport);
+        get(&installation);
+        get(&installation);
+        assert_eq!(testing::mints(&transport), 2);
+    }
+
+    #[test]
+    fn revoke_deletes_the_token() {
+        let transport = github(chrono::Duration::hours(1));
+        let installation = testing::installation(&transport);
+        // Nothing to revoke yet
+        installation.revoke().unwrap();
+        assert!(transport.requests().is_empty());
+
+        get(&installation);
+        installation.revoke().unwrap();
+        let revoke = transport.requests().pop().unwrap();
+        assert_eq!(revoke.method, Method::Delete);
+        assert_eq!(revoke.url, "https://api.github.com/installation/token");
+        assert_eq!(
+            revoke.headers[0],
+            ("Authorization".to_string(), "token t1".to_string())
+        );
+        assert!(installation.current_token().is_none());
+
+        get(&installation);
+        assert_eq!(testing::mints(&transport), 2);
+        assert_eq!(installation.current_token().unwrap().token, "t2");
+    }
+
+    #[test]
+    fn scoped_token_reports_what_was_granted() {
+        let transport = MockTransport::new(|request| {
+            if request.url.ends_with("/access_tokens") {
+                let body = json!({
+                    "token": "ghs_scoped",
+                    "expires_at": Utc::now() + chrono::Duration::hours(1),
+                    "permissions": {"contents": "read", "metadata": "read"},
+                    "repository_selection": "selected",
+                    // Token responses carry full repositories, which vary
+                    // between GitHub versions; only the basics are kept
+                    "repositories": [{
+                        "id": 186853002,
+                        "node_id": "MDEwOlJlcG9zaXRvcnkxODY4NTMwMDI=",
+                        "name": "Hello-World",
+                        "full_name": "Codertocat/Hello-World",
+                        "private": false,
+                        "pushed_at": null,
+                    }],
+                });
+                return testing::response(201, &[], &body.to_string());
+            }
+            testing::response(200, &[], "{}")
+        });
+        let installation = testing::installation(&transport).scoped(
+            TokenRequest::new()
+                .repository("Hello-World")
+                .permission("contents", "read"),
+        );
+        assert!(installation.current_token().is_none());
+        let _: serde_json::Value = installation.get_json(installation.api("zen")).unwrap();
+
+        let token = installation.current_token().unwrap();
+        assert_eq!(token.token, "ghs_scoped");
+        assert_eq!(token.permissions["contents"], "read");
+        assert_eq!(token.repository_selection.as_deref(), Some("selected"));
+        assert_eq!(
+            token.repositories.unwrap()[0].full_name,
+            "Codertocat/Hello-World"
+        );
+
+        let requests = transport.requests();
+        let body: serde_json::Value =
+            serde_json::from_slice(requests[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"repositories": ["Hello-World"], "permissions": {"contents": "read"}})
+        );
+        assert_eq!(
+            requests[1].headers[0],
+            ("Authorization".to_string(), "token ghs_scoped".to_string())
+        );
+    }
 }
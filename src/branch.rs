@@ -0,0 +1,282 @@
+use failure::Error;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::git::GitRef;
+use crate::pagination::encode;
+use crate::{AppInstallation, GithubError, Paginated, Repo};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Branch {
+    pub name: String,
+    pub commit: BranchCommit,
+    pub protected: bool,
+    pub protection_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BranchCommit {
+    pub sha: String,
+    pub url: String,
+}
+
+/// The rules protecting a branch. Rules that aren't enabled are `None`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BranchProtection {
+    pub url: String,
+    pub required_status_checks: Option<RequiredStatusChecks>,
+    pub required_pull_request_reviews: Option<RequiredReviews>,
+    pub enforce_admins: Option<Enabled>,
+    /// Who may push, as users, teams and apps.
+    pub restrictions: Option<serde_json::Value>,
+    pub required_linear_history: Option<Enabled>,
+    pub allow_force_pushes: Option<Enabled>,
+    pub allow_deletions: Option<Enabled>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct RequiredStatusChecks {
+    /// Whether branches must be up to date with the base before merging.
+    pub strict: bool,
+    #[serde(default)]
+    pub checks: Vec<StatusCheck>,
+    /// The names in `checks`, and all that older GitHub Enterprise versions
+    /// report. GitHub requires this when updating protection, so if it's
+    /// empty the names from `checks` are sent.
+    #[serde(default)]
+    pub contexts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StatusCheck {
+    pub context: String,
+    /// Only accept this check from one app. `None` leaves the app out so
+    /// GitHub picks the one that last set this check, and `Some(-1)` accepts
+    /// it from any app.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct RequiredReviews {
+    #[serde(default)]
+    pub dismiss_stale_reviews: bool,
+    #[serde(default)]
+    pub require_code_owner_reviews: bool,
+    #[serde(default)]
+    pub required_approving_review_count: usize,
+    #[serde(default)]
+    pub require_last_push_approval: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Enabled {
+    pub enabled: bool,
+}
+
+/// The complete set of rules for `Repo::update_branch_protection`. This
+/// replaces whatever protection the branch had, so `None` turns a rule off.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BranchProtectionUpdate {
+    pub required_status_checks: Option<RequiredStatusChecks>,
+    pub required_pull_request_reviews: Option<RequiredReviews>,
+    pub enforce_admins: bool,
+    pub restrictions: Option<PushRestrictions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_linear_history: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_force_pushes: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_deletions: Option<bool>,
+}
+
+/// Limits pushes to these user logins, team slugs and app slugs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PushRestrictions {
+    pub users: Vec<String>,
+    pub teams: Vec<String>,
+    pub apps: Vec<String>,
+}
+
+impl Serialize for RequiredStatusChecks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let contexts: Vec<&str> = if self.contexts.is_empty() {
+            self.checks
+                .iter()
+                .map(|check| check.context.as_str())
+                .collect()
+        } else {
+            self.contexts.iter().map(String::as_str).collect()
+        };
+        let mut state = serializer.serialize_struct("RequiredStatusChecks", 3)?;
+        state.serialize_field("strict", &self.strict)?;
+        if self.checks.is_empty() {
+            state.skip_field("checks")?;
+        } else {
+            state.serialize_field("checks", &self.checks)?;
+        }
+        state.serialize_field("contexts", &contexts)?;
+        state.end()
+    }
+}
+
+impl Repo {
+    pub fn branches(&self, installation: &AppInstallation) -> Result<Vec<Branch>, Error> {
+        self.iter_branches(installation).collect()
+    }
+
+    pub fn iter_branches<'a>(&self, installation: &'a AppInstallation) -> Paginated<'a, Branch> {
+        installation.paginate(installation.api(format!("repos/{}/branches", self.full_name)))
+    }
+
+    pub fn branch<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<Branch, Error> {
+        installation.get_json(self.branch_url(installation, name.as_ref()))
+    }
+
+    /// Creates branch `name` pointing at the commit `sha`.
+    pub fn create_branch<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T1,
+        sha: T2,
+    ) -> Result<GitRef, Error> {
+        self.create_ref(installation, format!("refs/heads/{}", name.as_ref()), sha)
+    }
+
+    pub fn delete_branch<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<(), Error> {
+        self.delete_ref(installation, format!("heads/{}", name.as_ref()))
+    }
+
+    /// The branch's protection rules, or `None` if it isn't protected.
+    pub fn branch_protection<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<Option<BranchProtection>, Error> {
+        let url = format!(
+            "{}/protection",
+            self.branch_url(installation, name.as_ref())
+        );
+        match installation.get_json(url) {
+            Ok(protection) => Ok(Some(protection)),
+            Err(e) => match e.downcast::<GithubError>() {
+                Ok(GithubError::NotFound { .. }) => Ok(None),
+                Ok(e) => Err(e.into()),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    pub fn update_branch_protection<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+        protection: &BranchProtectionUpdate,
+    ) -> Result<BranchProtection, Error> {
+        installation.put_json(
+            format!(
+                "{}/protection",
+                self.branch_url(installation, name.as_ref())
+            ),
+            protection,
+        )
+    }
+
+    pub fn remove_branch_protection<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<(), Error> {
+        installation.delete(format!(
+            "{}/protection",
+            self.branch_url(installation, name.as_ref())
+        ))
+    }
+
+    fn branch_url(&self, installation: &AppInstallation, name: &str) -> String {
+        // Slashes in branch names stay as they are
+        let name: Vec<String> = name.split('/').map(encode).collect();
+        installation.api(format!(
+            "repos/{}/branches/{}",
+            self.full_name,
+            name.join("/")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn contexts_are_filled_in_from_checks() {
+        let checks = RequiredStatusChecks {
+            strict: true,
+            checks: vec![
+                StatusCheck {
+                    context: "ci/build".into(),
+                    app_id: Some(31016),
+                },
+                StatusCheck {
+                    context: "lint".into(),
+                    app_id: None,
+                },
+                StatusCheck {
+                    context: "dco".into(),
+                    app_id: Some(-1),
+                },
+            ],
+            contexts: vec![],
+        };
+        assert_eq!(
+            serde_json::to_value(&checks).unwrap(),
+            json!({
+                "strict": true,
+                "checks": [
+                    {"context": "ci/build", "app_id": 31016},
+                    {"context": "lint"},
+                    {"context": "dco", "app_id": -1},
+                ],
+                "contexts": ["ci/build", "lint", "dco"],
+            })
+        );
+    }
+
+    #[test]
+    fn enterprise_contexts_round_trip() {
+        // Older GitHub Enterprise Server releases only report contexts
+        let checks: RequiredStatusChecks = serde_json::from_value(json!({
+            "url": "https://github.example.com/api/v3/repos/o/r/branches/main/protection/required_status_checks",
+            "strict": false,
+            "contexts": ["ci/build"],
+            "contexts_url": "https://github.example.com/api/v3/repos/o/r/branches/main/protection/required_status_checks/contexts",
+        }))
+        .unwrap();
+        assert!(checks.checks.is_empty());
+        assert_eq!(
+            serde_json::to_value(&checks).unwrap(),
+            json!({"strict": false, "contexts": ["ci/build"]})
+        );
+    }
+
+    #[test]
+    fn update_always_sends_contexts() {
+        let update = BranchProtectionUpdate {
+            required_status_checks: Some(RequiredStatusChecks::default()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap()["required_status_checks"],
+            json!({"strict": false, "contexts": []})
+        );
+    }
+}
@@ -0,0 +1,508 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::pagination::encode;
+use crate::{AppInstallation, Paginated, Repo};
+
+/// GitHub rejects check run requests carrying more than this many annotations.
+const MAX_ANNOTATIONS: usize = 50;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Queued,
+    InProgress,
+    Completed,
+    Waiting,
+    Requested,
+    Pending,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Failure,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckRun {
+    pub id: usize,
+    pub node_id: String,
+    pub name: String,
+    pub head_sha: String,
+    pub external_id: Option<String>,
+    pub url: String,
+    pub html_url: Option<String>,
+    pub details_url: Option<String>,
+    pub status: CheckStatus,
+    pub conclusion: Option<Conclusion>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub output: CheckRunOutputSummary,
+    pub check_suite: Option<CheckSuiteId>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckRunOutputSummary {
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub text: Option<String>,
+    pub annotations_count: usize,
+    pub annotations_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckSuiteId {
+    pub id: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckSuite {
+    pub id: usize,
+    pub node_id: String,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub status: Option<CheckStatus>,
+    pub conclusion: Option<Conclusion>,
+    pub url: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub latest_check_runs_count: Option<usize>,
+    pub check_runs_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Annotation {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+    pub annotation_level: AnnotationLevel,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_details: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CheckRunImage {
+    pub alt: String,
+    pub image_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+}
+
+/// The `output` section of a check run. Any number of annotations may be
+/// given; they're sent to GitHub in batches of 50.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CheckRunOutput {
+    pub title: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<CheckRunImage>,
+}
+
+/// A button shown on the check run that sends a `requested_action`
+/// `check_run` webhook with `identifier` when clicked.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CheckRunAction {
+    pub label: String,
+    pub description: String,
+    pub identifier: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NewCheckRun {
+    pub name: String,
+    pub head_sha: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CheckStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<Conclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckRunOutput>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<CheckRunAction>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct UpdateCheckRun {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CheckStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<Conclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckRunOutput>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<CheckRunAction>,
+}
+
+#[derive(Deserialize)]
+struct CheckRunList {
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Deserialize)]
+struct CheckSuiteList {
+    check_suites: Vec<CheckSuite>,
+}
+
+#[derive(Serialize)]
+struct NewCheckSuite<'a> {
+    head_sha: &'a str,
+}
+
+impl CheckRun {
+    pub fn annotations(&self, installation: &AppInstallation) -> Result<Vec<Annotation>, Error> {
+        self.iter_annotations(installation).collect()
+    }
+
+    pub fn iter_annotations<'a>(
+        &self,
+        installation: &'a AppInstallation,
+    ) -> Paginated<'a, Annotation> {
+        installation.paginate(&self.output.annotations_url)
+    }
+}
+
+impl Repo {
+    pub fn create_check_run(
+        &self,
+        installation: &AppInstallation,
+        check_run: &NewCheckRun,
+    ) -> Result<CheckRun, Error> {
+        let mut check_run = check_run.clone();
+        let remaining = split_annotations(&mut check_run.output);
+        let created: CheckRun =
+            installation.post_json(self.checks_url(installation, "check-runs"), &check_run)?;
+        self.send_annotations(installation, created, check_run.output, remaining)
+    }
+
+    pub fn update_check_run(
+        &self,
+        installation: &AppInstallation,
+        check_run_id: usize,
+        update: &UpdateCheckRun,
+    ) -> Result<CheckRun, Error> {
+        let mut update = update.clone();
+        let remaining = split_annotations(&mut update.output);
+        let updated: CheckRun = installation.patch_json(
+            self.checks_url(installation, format!("check-runs/{}", check_run_id)),
+            &update,
+        )?;
+        self.send_annotations(installation, updated, update.output, remaining)
+    }
+
+    pub fn check_run(
+        &self,
+        installation: &AppInstallation,
+        check_run_id: usize,
+    ) -> Result<CheckRun, Error> {
+        installation.get_json(self.checks_url(installation, format!("check-runs/{}", check_run_id)))
+    }
+
+    /// `git_ref` may be a SHA, branch name or tag name.
+    pub fn check_runs<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<Vec<CheckRun>, Error> {
+        self.iter_check_runs(installation, git_ref).collect()
+    }
+
+    pub fn iter_check_runs<'a, T: AsRef<str>>(
+        &self,
+        installation: &'a AppInstallation,
+        git_ref: T,
+    ) -> Paginated<'a, CheckRun> {
+        installation.paginate_with(
+            installation.api(format!(
+                "repos/{}/commits/{}/check-runs",
+                self.full_name,
+                encode(git_ref.as_ref())
+            )),
+            parse_check_runs,
+        )
+    }
+
+    pub fn check_runs_in_suite(
+        &self,
+        installation: &AppInstallation,
+        check_suite_id: usize,
+    ) -> Result<Vec<CheckRun>, Error> {
+        self.iter_check_runs_in_suite(installation, check_suite_id)
+            .collect()
+    }
+
+    pub fn iter_check_runs_in_suite<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        check_suite_id: usize,
+    ) -> Paginated<'a, CheckRun> {
+        installation.paginate_with(
+            self.checks_url(
+                installation,
+                format!("check-suites/{}/check-runs", check_suite_id),
+            ),
+            parse_check_runs,
+        )
+    }
+
+    pub fn rerequest_check_run(
+        &self,
+        installation: &AppInstallation,
+        check_run_id: usize,
+    ) -> Result<(), Error> {
+        installation.post(
+            self.checks_url(
+                installation,
+                format!("check-runs/{}/rerequest", check_run_id),
+            ),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn check_suite(
+        &self,
+        installation: &AppInstallation,
+        check_suite_id: usize,
+    ) -> Result<CheckSuite, Error> {
+        installation
+            .get_json(self.checks_url(installation, format!("check-suites/{}", check_suite_id)))
+    }
+
+    /// `git_ref` may be a SHA, branch name or tag name.
+    pub fn check_suites<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<Vec<CheckSuite>, Error> {
+        self.iter_check_suites(installation, git_ref).collect()
+    }
+
+    pub fn iter_check_suites<'a, T: AsRef<str>>(
+        &self,
+        installation: &'a AppInstallation,
+        git_ref: T,
+    ) -> Paginated<'a, CheckSuite> {
+        installation.paginate_with(
+            installation.api(format!(
+                "repos/{}/commits/{}/check-suites",
+                self.full_name,
+                encode(git_ref.as_ref())
+            )),
+            |body| Ok(serde_json::from_slice::<CheckSuiteList>(body)?.check_suites),
+        )
+    }
+
+    /// Only needed when automatic check suite creation has been disabled.
+    pub fn create_check_suite<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        head_sha: T,
+    ) -> Result<CheckSuite, Error> {
+        installation.post_json(
+            self.checks_url(installation, "check-suites"),
+            &NewCheckSuite {
+                head_sha: head_sha.as_ref(),
+            },
+        )
+    }
+
+    pub fn rerequest_check_suite(
+        &self,
+        installation: &AppInstallation,
+        check_suite_id: usize,
+    ) -> Result<(), Error> {
+        installation.post(
+            self.checks_url(
+                installation,
+                format!("check-suites/{}/rerequest", check_suite_id),
+            ),
+            None,
+        )?;
+        Ok(())
+    }
+
+    fn checks_url<T: AsRef<str>>(&self, installation: &AppInstallation, path: T) -> String {
+        installation.api(format!("repos/{}/{}", self.full_name, path.as_ref()))
+    }
+
+    /// Sends annotations beyond the first batch as follow-up updates.
+    fn send_annotations(
+        &self,
+        installation: &AppInstallation,
+        mut check_run: CheckRun,
+        output: Option<CheckRunOutput>,
+        remaining: Vec<Annotation>,
+    ) -> Result<CheckRun, Error> {
+        let output = match output {
+            Some(output) => output,
+            None => return Ok(check_run),
+        };
+        for batch in remaining.chunks(MAX_ANNOTATIONS) {
+            let update = UpdateCheckRun {
+                output: Some(CheckRunOutput {
+                    title: output.title.clone(),
+                    summary: output.summary.clone(),
+                    annotations: batch.to_vec(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            check_run = installation.patch_json(
+                self.checks_url(installation, format!("check-runs/{}", check_run.id)),
+                &update,
+            )?;
+        }
+        Ok(check_run)
+    }
+}
+
+/// Trims `output` down to the first batch of annotations and returns the rest.
+fn split_annotations(output: &mut Option<CheckRunOutput>) -> Vec<Annotation> {
+    match output {
+        Some(output) if output.annotations.len() > MAX_ANNOTATIONS => {
+            output.annotations.split_off(MAX_ANNOTATIONS)
+        }
+        _ => vec![],
+    }
+}
+
+fn parse_check_runs(body: &[u8]) -> Result<Vec<CheckRun>, Error> {
+    Ok(serde_json::from_slice::<CheckRunList>(body)?.check_runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn output(annotations: usize) -> Option<CheckRunOutput> {
+        Some(CheckRunOutput {
+            title: "Lint".into(),
+            summary: format!("{} warnings", annotations),
+            annotations: (0..annotations)
+                .map(|line| Annotation {
+                    path: "src/lib.rs".into(),
+                    start_line: line,
+                    end_line: line,
+                    start_column: None,
+                    end_column: None,
+                    annotation_level: AnnotationLevel::Warning,
+                    message: "unused variable".into(),
+                    title: None,
+                    raw_details: None,
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn split_annotations_without_output() {
+        let mut none = None;
+        assert!(split_annotations(&mut none).is_empty());
+        assert!(none.is_none());
+    }
+
+    #[test]
+    fn split_annotations_within_one_batch() {
+        for count in &[0, 1, MAX_ANNOTATIONS] {
+            let mut output = output(*count);
+            assert!(split_annotations(&mut output).is_empty());
+            assert_eq!(output.unwrap().annotations.len(), *count);
+        }
+    }
+
+    #[test]
+    fn split_annotations_keeps_the_first_batch() {
+        let mut output = output(120);
+        let remaining = split_annotations(&mut output);
+        let first = output.unwrap().annotations;
+        assert_eq!(first.len(), MAX_ANNOTATIONS);
+        assert_eq!(first[0].start_line, 0);
+        assert_eq!(remaining.len(), 70);
+        assert_eq!(remaining[0].start_line, MAX_ANNOTATIONS);
+        assert_eq!(remaining[69].start_line, 119);
+        assert_eq!(remaining.chunks(MAX_ANNOTATIONS).count(), 2);
+    }
+
+    #[test]
+    fn refs_are_encoded_like_statuses() {
+        let transport = testing::github(|request| {
+            if request.url.contains("/statuses") {
+                return testing::response(200, &[], "[]");
+            }
+            testing::response(
+                200,
+                &[],
+                r#"{"total_count": 0, "check_runs": [], "check_suites": []}"#,
+            )
+        });
+        let installation = testing::installation(&transport);
+        let repo = testing::repo();
+        repo.check_runs(&installation, "feature/a b").unwrap();
+        repo.check_suites(&installation, "feature/a b").unwrap();
+        repo.statuses(&installation, "feature/a b").unwrap();
+        let urls: Vec<String> = transport
+            .requests()
+            .into_iter()
+            .skip(1)
+            .map(|request| request.url)
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://api.github.com/repos/Codertocat/Hello-World/commits/feature%2Fa%20b/check-runs?per_page=100",
+                "https://api.github.com/repos/Codertocat/Hello-World/commits/feature%2Fa%20b/check-suites?per_page=100",
+                "https://api.github.com/repos/Codertocat/Hello-World/commits/feature%2Fa%20b/statuses?per_page=100",
+            ]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::{Account, AppInstallation, Paginated, PullRequest, Repo};
+
+/// A comment on the conversation of an issue or pull request. Inline
+/// comments on a pull request's diff are `ReviewComment`s instead.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssueComment {
+    pub id: usize,
+    pub node_id: String,
+    pub url: String,
+    pub html_url: String,
+    pub issue_url: String,
+    #[serde(default)]
+    pub body: String,
+    pub user: Option<Account>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub author_association: String,
+}
+
+impl IssueComment {
+    /// Whether the comment was posted by `sticky_comment` with this marker.
+    pub fn has_marker<T: AsRef<str>>(&self, marker: T) -> bool {
+        self.body.contains(&hidden_marker(marker.as_ref()))
+    }
+}
+
+// Pull requests are issues as far as comments go, so these all take the
+// issue or pull request number.
+impl Repo {
+    pub fn issue_comments(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+    ) -> Result<Vec<IssueComment>, Error> {
+        self.iter_issue_comments(installation, number).collect()
+    }
+
+    pub fn iter_issue_comments<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        number: usize,
+    ) -> Paginated<'a, IssueComment> {
+        installation.paginate(self.issues_api_url(installation, format!("{}/comments", number)))
+    }
+
+    pub fn create_issue_comment<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        body: T,
+    ) -> Result<IssueComment, Error> {
+        installation.post_json(
+            self.issues_api_url(installation, format!("{}/comments", number)),
+            &serde_json::json!({ "body": body.as_ref() }),
+        )
+    }
+
+    pub fn edit_issue_comment<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        comment_id: usize,
+        body: T,
+    ) -> Result<IssueComment, Error> {
+        installation.patch_json(
+            self.issues_api_url(installation, format!("comments/{}", comment_id)),
+            &serde_json::json!({ "body": body.as_ref() }),
+        )
+    }
+
+    pub fn delete_issue_comment(
+        &self,
+        installation: &AppInstallation,
+        comment_id: usize,
+    ) -> Result<(), Error> {
+        installation.delete(self.issues_api_url(installation, format!("comments/{}", comment_id)))
+    }
+
+    /// Keeps a single, updatable comment per `marker` on an issue or pull
+    /// request. The marker is stored in a hidden HTML comment in the body;
+    /// if a bot comment carrying it already exists it is edited in place,
+    /// otherwise a new one is posted. Comments by people are never matched,
+    /// even when they quote the marker.
+    pub fn sticky_comment<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        marker: T1,
+        body: T2,
+    ) -> Result<IssueComment, Error> {
+        let marker = marker.as_ref();
+        let body = format!("{}\n{}", hidden_marker(marker), body.as_ref());
+        for comment in self.iter_issue_comments(installation, number) {
+            let comment = comment?;
+            if comment.has_marker(marker) && posted_by_bot(&comment) {
+                if comment.body == body {
+                    return Ok(comment);
+                }
+                return self.edit_issue_comment(installation, comment.id, body);
+            }
+        }
+        self.create_issue_comment(installation, number, body)
+    }
+
+    pub(crate) fn issues_api_url<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+    ) -> String {
+        installation.api(format!("repos/{}/issues/{}", self.full_name, path.as_ref()))
+    }
+}
+
+impl PullRequest {
+    pub fn comments(&self, installation: &AppInstallation) -> Result<Vec<IssueComment>, Error> {
+        self.iter_comments(installation).collect()
+    }
+
+    pub fn iter_comments<'a>(
+        &self,
+        installation: &'a AppInstallation,
+    ) -> Paginated<'a, IssueComment> {
+        self.base
+            .repo
+            .iter_issue_comments(installation, self.number)
+    }
+
+    pub fn create_comment<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        body: T,
+    ) -> Result<IssueComment, Error> {
+        self.base
+            .repo
+            .create_issue_comment(installation, self.number, body)
+    }
+
+    /// See `Repo::sticky_comment`.
+    pub fn sticky_comment<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        marker: T1,
+        body: T2,
+    ) -> Result<IssueComment, Error> {
+        self.base
+            .repo
+            .sticky_comment(installation, self.number, marker, body)
+    }
+}
+
+fn hidden_marker(marker: &str) -> String {
+    format!("<!-- {} -->", marker)
+}
+
+// Installations comment as `<app slug>[bot]`
+fn posted_by_bot(comment: &IssueComment) -> bool {
+    match comment.user {
+        Some(ref user) => user.user_type == "Bot" && user.login.ends_with("[bot]"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::testing;
+    use crate::transport::Method;
+
+    const COMMENTS: &str =
+        "https://api.github.com/repos/Codertocat/Hello-World/issues/1/comments?per_page=100";
+
+    /// A comment on Hello-World#1 by `login`.
+    fn comment(id: usize, login: &str, user_type: &str, body: &str) -> Value {
+        let event: Value = serde_json::from_slice(include_bytes!(
+            "../tests/fixtures/events/issue_comment.json"
+        ))
+        .unwrap();
+        let mut comment = event["comment"].clone();
+        comment["id"] = json!(id);
+        comment["body"] = json!(body);
+        comment["user"]["login"] = json!(login);
+        comment["user"]["type"] = json!(user_type);
+        comment
+    }
+
+    fn sticky(existing: Vec<Value>, body: &str) -> testing::MockTransport {
+        let transport = testing::github(move |request| match request.method {
+            Method::Get if request.url == COMMENTS => {
+                testing::response(200, &[], &Value::from(existing.clone()).to_string())
+            }
+            Method::Post | Method::Patch => {
+                let sent: Value = serde_json::from_slice(request.body.as_ref().unwrap()).unwrap();
+                let body = sent["body"].as_str().unwrap();
+                let posted = comment(3, "my-app[bot]", "Bot", body);
+                testing::response(200, &[], &posted.to_string())
+            }
+            _ => panic!("unexpected {:?} {}", request.method, request.url),
+        });
+        let installation = testing::installation(&transport);
+        let posted = testing::repo()
+            .sticky_comment(&installation, 1, "coverage", body)
+            .unwrap();
+        assert_eq!(posted.body, format!("<!-- coverage -->\n{}", body));
+        transport
+    }
+
+    #[test]
+    fn sticky_comment_is_created() {
+        // A person quoting the marker doesn't count
+        let existing = vec![comment(
+            1,
+            "Codertocat",
+            "User",
+            "Why does <!-- coverage --> show up here?",
+        )];
+        let requests = sticky(existing, "92%").requests();
+        let create = requests.last().unwrap();
+        assert_eq!(create.method, Method::Post);
+        assert_eq!(
+            create.url,
+            "https://api.github.com/repos/Codertocat/Hello-World/issues/1/comments"
+        );
+    }
+
+    #[test]
+    fn sticky_comment_is_edited_in_place() {
+        let existing = vec![
+            comment(1, "Codertocat", "User", "<!-- coverage -->\n100%"),
+            comment(2, "my-app[bot]", "Bot", "<!-- coverage -->\n91%"),
+        ];
+        let requests = sticky(existing, "92%").requests();
+        let edit = requests.last().unwrap();
+        assert_eq!(edit.method, Method::Patch);
+        assert_eq!(
+            edit.url,
+            "https://api.github.com/repos/Codertocat/Hello-World/issues/comments/2"
+        );
+    }
+
+    #[test]
+    fn sticky_comment_unchanged_is_left_alone() {
+        let existing = vec![comment(2, "my-app[bot]", "Bot", "<!-- coverage -->\n92%")];
+        let transport = sticky(existing, "92%");
+        // Minting a token and listing the comments is all it takes
+        assert_eq!(transport.requests().len(), 2);
+        assert_eq!(transport.requests()[1].url, COMMENTS);
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::prelude::*;
+
+use crate::Account;
+
+/// A commit as returned by the REST API, with the GitHub users it's
+/// attributed to alongside the raw git data.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Commit {
+    pub sha: String,
+    pub node_id: String,
+    pub url: String,
+    pub html_url: String,
+    pub comments_url: String,
+    pub commit: CommitDetails,
+    /// `None` when the author's email doesn't match a GitHub account.
+    pub author: Option<Account>,
+    pub committer: Option<Account>,
+    pub parents: Vec<CommitParent>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CommitDetails {
+    pub url: String,
+    pub message: String,
+    pub author: Option<Signature>,
+    pub committer: Option<Signature>,
+    pub tree: CommitParent,
+    pub comment_count: usize,
+}
+
+/// The name, email and time git records for a commit's author or committer.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CommitParent {
+    pub sha: String,
+    pub url: String,
+}
+
+/// A commit as the Git Data API sees it: just the git object, without any
+/// GitHub accounts attached.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitCommit {
+    pub sha: String,
+    pub node_id: String,
+    pub url: String,
+    pub html_url: Option<String>,
+    pub message: String,
+    pub author: Signature,
+    pub committer: Signature,
+    pub tree: CommitParent,
+    pub parents: Vec<CommitParent>,
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+
+use crate::contents::{contents_url, Content};
+use crate::transport::Method;
+use crate::{AppInstallation, GithubError, Repo};
+
+/// Repositories that hold config shared across an organisation.
+const ORG_CONFIG_REPO: &str = ".github";
+const EXTENDS_KEY: &str = "_extends";
+/// Guards against `_extends` cycles.
+const MAX_EXTENDS_DEPTH: usize = 5;
+
+/// Parsed config files, shared by every `Repo::config` call on an
+/// installation.
+///
+/// Parsed files are keyed by blob SHA, so identical files are only parsed
+/// once. The ETag of each contents URL is kept too, so that re-reading an
+/// unchanged file is a conditional request that doesn't count against the
+/// rate limit.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigCache {
+    etags: HashMap<String, (String, String)>,
+    blobs: HashMap<String, Value>,
+}
+
+impl Repo {
+    /// Loads `.github/<file_name>` (e.g. `my-app.yml`) from this repository at
+    /// `git_ref`, or from the default branch if that's `None`, in the way
+    /// Probot's `context.config` does:
+    ///
+    /// - if the repository has no such file, the organisation's `.github`
+    ///   repository is tried instead;
+    /// - a top-level `_extends: other-repo` key, optionally `owner/repo` or
+    ///   `repo:path/to/file.yml`, inherits from another file. Mappings are
+    ///   merged recursively with this file's values taking precedence; any
+    ///   other value, including lists, is replaced outright.
+    ///
+    /// Returns `None` when there is no config at all.
+    pub fn config<T: DeserializeOwned>(
+        &self,
+        installation: &AppInstallation,
+        file_name: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<T>, Error> {
+        let path = format!(".github/{}", file_name);
+        let mut config = load_yaml(installation, &self.full_name, &path, git_ref)?;
+        if config.is_none() && self.name != ORG_CONFIG_REPO {
+            let org_repo = format!("{}/{}", self.owner.login, ORG_CONFIG_REPO);
+            debug!("No {} in {}, trying {}", path, self.full_name, org_repo);
+            config = load_yaml(installation, &org_repo, &path, None)?;
+        }
+        let mut config = match config {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        // Relative `_extends` resolve against the owner of the file they're
+        // in, which changes as the chain crosses organisations
+        let mut owner = self.owner.login.clone();
+        let mut depth = 0;
+        while let Some(extends) = take_extends(&mut config)? {
+            depth += 1;
+            if depth > MAX_EXTENDS_DEPTH {
+                return Err(format_err!(
+                    "{} extends more than {} levels deep",
+                    path,
+                    MAX_EXTENDS_DEPTH
+                ));
+            }
+            let (repo, base_path) = resolve_extends(&extends, &owner, &path);
+            debug!("{} extends {}:{}", path, repo, base_path);
+            let base = load_yaml(installation, &repo, &base_path, None)?.ok_or_else(|| {
+                format_err!(
+                    "{} extends {}:{}, which doesn't exist",
+                    path,
+                    repo,
+                    base_path
+                )
+            })?;
+            config = merge(base, config);
+            owner = repo.split('/').next().unwrap_or_default().to_string();
+        }
+        Ok(Some(serde_yaml::from_value(config)?))
+    }
+}
+
+/// Fetches and parses a YAML file, going through the installation's
+/// `ConfigCache`. A missing file is `None`, an empty one an empty mapping.
+fn load_yaml(
+    installation: &AppInstallation,
+    full_name: &str,
+    path: &str,
+    git_ref: Option<&str>,
+) -> Result<Option<Value>, Error> {
+    let url = contents_url(installation, full_name, path, git_ref);
+    let etag = installation
+        .config_cache
+        .lock()
+        .unwrap()
+        .etags
+        .get(&url)
+        .cloned();
+    let headers = match etag {
+        Some((ref etag, _)) => vec![("If-None-Match".to_string(), etag.clone())],
+        None => vec![],
+    };
+    let response = match installation.request_with_headers(Method::Get, &url, headers, None) {
+        Ok(response) => response,
+        Err(e) => match e.downcast::<GithubError>() {
+            Ok(GithubError::NotFound { .. }) => return Ok(None),
+            Ok(e) => return Err(e.into()),
+            Err(e) => return Err(e),
+        },
+    };
+    let mut cache = installation.config_cache.lock().unwrap();
+    if response.status == 304 {
+        let cached = etag.and_then(|(_, sha)| cache.blobs.get(&sha).cloned());
+        if cached.is_some() {
+            trace!("{}:{} is unchanged", full_name, path);
+            return Ok(cached);
+        }
+        // Shouldn't happen, but fall back to an unconditional request
+        cache.etags.remove(&url);
+        drop(cache);
+        return load_yaml(installation, full_name, path, git_ref);
+    }
+    let content: Content = serde_json::from_slice(&response.body)?;
+    let value = match cache.blobs.get(&content.sha) {
+        Some(value) => value.clone(),
+        None => {
+            let bytes = content.decoded()?.ok_or_else(|| {
+                format_err!("{}:{} is too large to be a config file", full_name, path)
+            })?;
+            match serde_yaml::from_slice(&bytes)? {
+                Value::Null => Value::Mapping(Mapping::new()),
+                value => value,
+            }
+        }
+    };
+    if let Some(etag) = response.header("ETag") {
+        cache
+            .etags
+            .insert(url, (etag.to_string(), content.sha.clone()));
+    }
+    cache.blobs.insert(content.sha, value.clone());
+    Ok(Some(value))
+}
+
+/// Turns an `_extends` value into the repository and path it refers to.
+/// `owner` fills in a bare repository name, and `path` a missing path.
+fn resolve_extends(extends: &str, owner: &str, path: &str) -> (String, String) {
+    let (repo, path) = match extends.find(':') {
+        Some(idx) => (&extends[..idx], &extends[idx + 1..]),
+        None => (extends, path),
+    };
+    let repo = if repo.contains('/') {
+        repo.to_string()
+    } else {
+        format!("{}/{}", owner, repo)
+    };
+    (repo, path.to_string())
+}
+
+fn take_extends(config: &mut Value) -> Result<Option<String>, Error> {
+    match config
+        .as_mapping_mut()
+        .and_then(|map| map.remove(EXTENDS_KEY))
+    {
+        None => Ok(None),
+        Some(Value::String(extends)) => Ok(Some(extends)),
+        Some(other) => Err(format_err!("_extends should be a string, not {:?}", other)),
+    }
+}
+
+fn merge(base: Value, overrides: Value) -> Value {
+    match (base, overrides) {
+        (Value::Mapping(mut base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                let value = match base.remove(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => value,
+                };
+                base.insert(key, value);
+            }
+            Value::Mapping(base)
+        }
+        (_, overrides) => overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::testing;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn merge_is_recursive_for_mappings() {
+        let base = yaml(
+            "
+            labels: [bug, feature]
+            review:
+              required: 2
+              teams: [core]
+            stale_days: 30
+            ",
+        );
+        let overrides = yaml(
+            "
+            labels: [docs]
+            review:
+              required: 1
+            enabled: true
+            ",
+        );
+        assert_eq!(
+            merge(base, overrides),
+            yaml(
+                "
+                labels: [docs]
+                review:
+                  required: 1
+                  teams: [core]
+                stale_days: 30
+                enabled: true
+                ",
+            )
+        );
+    }
+
+    #[test]
+    fn merge_replaces_other_values() {
+        assert_eq!(merge(yaml("a: 1"), yaml("[1, 2]")), yaml("[1, 2]"));
+        assert_eq!(merge(yaml("a: {b: 1}"), yaml("a: 2")), yaml("a: 2"));
+        assert_eq!(merge(yaml("a: 2"), yaml("a: {b: 1}")), yaml("a: {b: 1}"));
+        assert_eq!(merge(yaml("a: 1"), yaml("a: null")), yaml("a: null"));
+    }
+
+    #[test]
+    fn resolve_extends_fills_in_owner_and_path() {
+        let path = ".github/my-app.yml";
+        let resolve = |extends| resolve_extends(extends, "acme", path);
+        assert_eq!(resolve(".github"), ("acme/.github".into(), path.into()));
+        assert_eq!(
+            resolve("other-org/base"),
+            ("other-org/base".into(), path.into())
+        );
+        assert_eq!(
+            resolve("base:shared/app.yml"),
+            ("acme/base".into(), "shared/app.yml".into())
+        );
+        assert_eq!(
+            resolve("other-org/base:app.yml"),
+            ("other-org/base".into(), "app.yml".into())
+        );
+    }
+
+    #[test]
+    fn extends_chain_crosses_organisations() {
+        let transport = testing::github(|request| {
+            let file = match request.url.as_str() {
+                "https://api.github.com/repos/Codertocat/Hello-World/contents/.github/my-app.yml" => {
+                    "_extends: other-org/base\nlabels: [bug]\n"
+                }
+                // `defaults` is relative to other-org, not Codertocat
+                "https://api.github.com/repos/other-org/base/contents/.github/my-app.yml" => {
+                    "_extends: defaults\nreview: {required: 2}\n"
+                }
+                "https://api.github.com/repos/other-org/defaults/contents/.github/my-app.yml" => {
+                    "labels: [feature]\nstale_days: 30\n"
+                }
+                _ => return testing::response(404, &[], r#"{"message": "Not Found"}"#),
+            };
+            let body = json!({
+                "type": "file",
+                "name": "my-app.yml",
+                "path": ".github/my-app.yml",
+                "sha": hex::encode(Sha256::digest(file)),
+                "size": file.len(),
+                "url": request.url,
+                "encoding": "base64",
+                "content": STANDARD.encode(file),
+            });
+            testing::response(200, &[], &body.to_string())
+        });
+        let installation = testing::installation(&transport);
+        let config: Value = testing::repo()
+            .config(&installation, "my-app.yml", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            config,
+            yaml("labels: [bug]\nreview: {required: 2}\nstale_days: 30")
+        );
+    }
+
+    #[test]
+    fn take_extends_removes_the_key() {
+        let mut config = yaml("_extends: .github\nlabels: [bug]");
+        assert_eq!(
+            take_extends(&mut config).unwrap().as_deref(),
+            Some(".github")
+        );
+        assert_eq!(config, yaml("labels: [bug]"));
+        assert_eq!(take_extends(&mut config).unwrap(), None);
+    }
+
+    #[test]
+    fn take_extends_without_a_mapping() {
+        assert_eq!(take_extends(&mut yaml("[1, 2]")).unwrap(), None);
+        assert_eq!(take_extends(&mut yaml("just a string")).unwrap(), None);
+    }
+
+    #[test]
+    fn take_extends_rejects_non_strings() {
+        assert!(take_extends(&mut yaml("_extends: [a, b]")).is_err());
+        assert!(take_extends(&mut yaml("_extends: 1")).is_err());
+    }
+}
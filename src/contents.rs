@@ -0,0 +1,307 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use failure::Error;
+
+use crate::pagination::{encode, with_query};
+use crate::transport::Method;
+use crate::{AppInstallation, GitCommit, Repo};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    File,
+    Dir,
+    Symlink,
+    Submodule,
+}
+
+/// An entry in a repository, as returned by the contents API. `content` is
+/// only filled in when fetching a single file; see `Repo::file` for the
+/// decoded bytes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Content {
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
+    pub name: String,
+    pub path: String,
+    pub sha: String,
+    pub size: usize,
+    pub url: String,
+    pub html_url: Option<String>,
+    pub git_url: Option<String>,
+    pub download_url: Option<String>,
+    pub encoding: Option<String>,
+    pub content: Option<String>,
+    /// Where a symlink points.
+    pub target: Option<String>,
+    pub submodule_git_url: Option<String>,
+}
+
+/// A file's contents along with the blob SHA needed to update or delete it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct File {
+    pub name: String,
+    pub path: String,
+    pub sha: String,
+    pub content: Vec<u8>,
+}
+
+/// Creates or replaces a file in a single commit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileUpdate {
+    pub message: String,
+    pub content: Vec<u8>,
+    /// The blob SHA of the file being replaced, or `None` to create a new
+    /// file. GitHub refuses the update with a 409 if the file has changed
+    /// since.
+    pub sha: Option<String>,
+    /// Defaults to the repository's default branch.
+    pub branch: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FileDelete {
+    pub message: String,
+    /// The blob SHA of the file being deleted.
+    pub sha: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FileCommit {
+    /// The new file, or `None` after a delete.
+    pub content: Option<Content>,
+    pub commit: GitCommit,
+}
+
+#[derive(Serialize)]
+struct FileUpdateBody<'a> {
+    message: &'a str,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<&'a str>,
+}
+
+impl Content {
+    /// Decodes `content`, which GitHub sends as line-wrapped base64.
+    pub fn decoded(&self) -> Result<Option<Vec<u8>>, Error> {
+        match (self.encoding.as_deref(), self.content.as_ref()) {
+            (Some("base64"), Some(content)) => {
+                let content: String = content.split_whitespace().collect();
+                Ok(Some(STANDARD.decode(content)?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl File {
+    pub fn text(&self) -> Result<&str, Error> {
+        Ok(std::str::from_utf8(&self.content)?)
+    }
+}
+
+impl Repo {
+    /// Looks up whatever is at `path`. `git_ref` may be a branch, tag or
+    /// commit SHA, and defaults to the default branch.
+    pub fn content<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+        git_ref: Option<&str>,
+    ) -> Result<Content, Error> {
+        installation.get_json(self.contents_api_url(installation, path.as_ref(), git_ref))
+    }
+
+    /// Fetches and decodes a file. Files over 1MB, which the contents API
+    /// doesn't inline, are downloaded separately.
+    pub fn file<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+        git_ref: Option<&str>,
+    ) -> Result<File, Error> {
+        let url = self.contents_api_url(installation, path.as_ref(), git_ref);
+        let content: Content = installation.get_json(&url)?;
+        if content.content_type != ContentType::File {
+            return Err(format_err!(
+                "{} is a {:?}, not a file",
+                content.path,
+                content.content_type
+            ));
+        }
+        let bytes = match content.decoded()? {
+            Some(bytes) => bytes,
+            None => {
+                debug!("{} is too large to inline, fetching it raw", content.path);
+                installation
+                    .request_with_headers(
+                        Method::Get,
+                        url,
+                        vec![(
+                            "Accept".to_string(),
+                            "application/vnd.github.v3.raw".to_string(),
+                        )],
+                        None,
+                    )?
+                    .body
+            }
+        };
+        Ok(File {
+            name: content.name,
+            path: content.path,
+            sha: content.sha,
+            content: bytes,
+        })
+    }
+
+    /// Lists a directory; use `""` for the root of the repository. The
+    /// contents API returns at most 1000 entries.
+    pub fn directory<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+        git_ref: Option<&str>,
+    ) -> Result<Vec<Content>, Error> {
+        let url = self.contents_api_url(installation, path.as_ref(), git_ref);
+        let listing: serde_json::Value = installation.get_json(url)?;
+        if listing.is_array() {
+            return Ok(serde_json::from_value(listing)?);
+        }
+        // Anything other than a directory comes back as a single object
+        let content: Content = serde_json::from_value(listing)?;
+        Err(format_err!(
+            "{} is a {:?}, not a directory",
+            content.path,
+            content.content_type
+        ))
+    }
+
+    pub fn create_or_update_file<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+        update: &FileUpdate,
+    ) -> Result<FileCommit, Error> {
+        installation.put_json(
+            self.contents_api_url(installation, path.as_ref(), None),
+            &FileUpdateBody {
+                message: &update.message,
+                content: STANDARD.encode(&update.content),
+                sha: update.sha.as_deref(),
+                branch: update.branch.as_deref(),
+            },
+        )
+    }
+
+    pub fn delete_file<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        path: T,
+        delete: &FileDelete,
+    ) -> Result<FileCommit, Error> {
+        installation.delete_json(
+            self.contents_api_url(installation, path.as_ref(), None),
+            delete,
+        )
+    }
+
+    fn contents_api_url(
+        &self,
+        installation: &AppInstallation,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> String {
+        contents_url(installation, &self.full_name, path, git_ref)
+    }
+}
+
+pub(crate) fn contents_url(
+    installation: &AppInstallation,
+    full_name: &str,
+    path: &str,
+    git_ref: Option<&str>,
+) -> String {
+    let path: Vec<String> = path.trim_matches('/').split('/').map(encode).collect();
+    let url = installation.api(format!("repos/{}/contents/{}", full_name, path.join("/")));
+    match git_ref {
+        Some(git_ref) => with_query(&url, "ref", &encode(git_ref)),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::testing;
+
+    const README: &str = "https://api.github.com/repos/Codertocat/Hello-World/contents/README";
+
+    fn content(encoding: &str, content: &str, size: usize) -> Value {
+        json!({
+            "type": "file",
+            "name": "README",
+            "path": "README",
+            "sha": "980a0d5f19a64b4b30a87d4206aade58726b60e3",
+            "size": size,
+            "url": README,
+            "html_url": "https://github.com/Codertocat/Hello-World/blob/master/README",
+            "git_url": "https://api.github.com/repos/Codertocat/Hello-World/git/blobs/980a0d5f19a64b4b30a87d4206aade58726b60e3",
+            "download_url": "https://raw.githubusercontent.com/Codertocat/Hello-World/master/README",
+            "encoding": encoding,
+            "content": content,
+        })
+    }
+
+    #[test]
+    fn file_decodes_wrapped_base64() {
+        let transport = testing::github(|_| {
+            // GitHub wraps the base64 every 60 characters
+            let body = content("base64", "SGVsbG8gV29ybGQh\nCg==\n", 13);
+            testing::response(200, &[], &body.to_string())
+        });
+        let installation = testing::installation(&transport);
+        let file = testing::repo().file(&installation, "README", None).unwrap();
+        assert_eq!(file.text().unwrap(), "Hello World!\n");
+        assert_eq!(file.sha, "980a0d5f19a64b4b30a87d4206aade58726b60e3");
+    }
+
+    #[test]
+    fn large_files_are_downloaded_raw() {
+        let transport = testing::github(|request| {
+            let raw = request
+                .headers
+                .iter()
+                .any(|(key, value)| key == "Accept" && value == "application/vnd.github.v3.raw");
+            if raw {
+                testing::response(200, &[], "Hello World!\n")
+            } else {
+                let body = content("none", "", 2 * 1024 * 1024);
+                testing::response(200, &[], &body.to_string())
+            }
+        });
+        let installation = testing::installation(&transport);
+        let file = testing::repo().file(&installation, "README", None).unwrap();
+        assert_eq!(file.text().unwrap(), "Hello World!\n");
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].url, README);
+    }
+
+    #[test]
+    fn directory_of_a_file_is_an_error() {
+        let transport = testing::github(|_| {
+            let body = content("base64", "SGVsbG8gV29ybGQh\nCg==\n", 13);
+            testing::response(200, &[], &body.to_string())
+        });
+        let installation = testing::installation(&transport);
+        let error = testing::repo()
+            .directory(&installation, "README", None)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "README is a File, not a directory");
+    }
+}
@@ -1,7 +1,8 @@
 use std::io;
 
-use curl;
-use jsonwebtoken;
+use chrono::prelude::*;
+
+use crate::transport::Response;
 
 #[derive(Fail, Debug)]
 pub enum GithubError {
@@ -13,6 +14,317 @@ pub enum GithubError {
     CurlError { error: curl::Error },
     #[fail(display = "A request was made without a token.")]
     MissingToken,
+    #[fail(display = "Not found: {}", message)]
+    NotFound {
+        message: String,
+        documentation_url: Option<String>,
+    },
+    #[fail(display = "Unauthorized: {}", message)]
+    Unauthorized {
+        message: String,
+        documentation_url: Option<String>,
+    },
+    #[fail(display = "Forbidden: {}", message)]
+    Forbidden {
+        message: String,
+        documentation_url: Option<String>,
+    },
+    #[fail(display = "Validation failed: {} {:?}", message, errors)]
+    ValidationFailed {
+        message: String,
+        errors: Vec<FieldError>,
+        documentation_url: Option<String>,
+    },
+    #[fail(display = "Rate limited: {}", message)]
+    RateLimited {
+        message: String,
+        reset: Option<DateTime<Utc>>,
+    },
+    #[fail(display = "Server error {}: {}", status, message)]
+    ServerError { status: u32, message: String },
+    #[fail(display = "HTTP error {}: {}", status, message)]
+    HttpError {
+        status: u32,
+        message: String,
+        documentation_url: Option<String>,
+    },
+    #[fail(display = "Pull request is not mergeable: {}", message)]
+    NotMergeable { message: String },
+    #[fail(display = "Pull request head has changed: {}", message)]
+    HeadShaMismatch { message: String },
+    #[fail(display = "Can't be assigned here: {:?}", logins)]
+    NotAssignable { logins: Vec<String> },
+    #[fail(display = "The webhook signature did not match the payload.")]
+    InvalidSignature,
+    #[fail(display = "Missing required header: {}", name)]
+    MissingHeader { name: String },
     #[fail(display = "An unknown error has occurred.")]
     UnknownError,
 }
+
+/// A single entry from the `errors` array of a GitHub error response.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FieldError {
+    pub resource: Option<String>,
+    pub field: Option<String>,
+    pub code: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    message: String,
+    documentation_url: Option<String>,
+    // GitHub sometimes sends bare strings here instead of objects
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+impl GithubError {
+    /// Passes 2xx and 304 Not Modified responses through and turns anything
+    /// else, including redirects that weren't followed, into the matching
+    /// `GithubError`.
+    pub(crate) fn check(response: Response) -> Result<Response, GithubError> {
+        match response.status {
+            200..=299 | 304 => Ok(response),
+            _ => Err(GithubError::from_response(&response)),
+        }
+    }
+
+    fn from_response(response: &Response) -> GithubError {
+        let body: ErrorBody = serde_json::from_slice(&response.body).unwrap_or_default();
+        let ErrorBody {
+            message,
+            documentation_url,
+            errors,
+        } = body;
+        let message = if message.is_empty() {
+            String::from_utf8_lossy(&response.body).into_owned()
+        } else {
+            message
+        };
+        let rate_limited = response.status == 429
+            || (response.status == 403
+                && (response.header("X-RateLimit-Remaining") == Some("0")
+                    || response.header("Retry-After").is_some()
+                    || message.to_lowercase().contains("rate limit")));
+        if rate_limited {
+            return GithubError::RateLimited {
+                message,
+                reset: reset_time(response),
+            };
+        }
+        match response.status {
+            401 => GithubError::Unauthorized {
+                message,
+                documentation_url,
+            },
+            403 => GithubError::Forbidden {
+                message,
+                documentation_url,
+            },
+            404 => GithubError::NotFound {
+                message,
+                documentation_url,
+            },
+            422 => GithubError::ValidationFailed {
+                message,
+                errors: errors.into_iter().map(field_error).collect(),
+                documentation_url,
+            },
+            status if status >= 500 => GithubError::ServerError { status, message },
+            status => GithubError::HttpError {
+                status,
+                message,
+                documentation_url,
+            },
+        }
+    }
+}
+
+fn field_error(value: serde_json::Value) -> FieldError {
+    match value {
+        serde_json::Value::String(message) => FieldError {
+            message: Some(message),
+            ..Default::default()
+        },
+        value => serde_json::from_value(value).unwrap_or_default(),
+    }
+}
+
+/// When a rate limited request may be retried, from either `Retry-After` or,
+/// if the primary limit is used up, `X-RateLimit-Reset`.
+fn reset_time(response: &Response) -> Option<DateTime<Utc>> {
+    if let Some(seconds) = response
+        .header("Retry-After")
+        .and_then(|s| s.parse::<i64>().ok())
+    {
+        return Some(Utc::now() + chrono::Duration::seconds(seconds));
+    }
+    if response.header("X-RateLimit-Remaining") != Some("0") {
+        return None;
+    }
+    response
+        .header("X-RateLimit-Reset")
+        .and_then(|s| s.parse::<i64>().ok())
+        .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::response;
+
+    #[test]
+    fn check_passes_success_and_not_modified() {
+        for status in &[200, 201, 204, 304] {
+            assert!(GithubError::check(response(*status, &[], "")).is_ok());
+        }
+    }
+
+    #[test]
+    fn check_rejects_redirects() {
+        let body =
+            r#"{"message": "Moved Permanently", "url": "https://api.github.com/repositories/1"}"#;
+        match GithubError::check(response(301, &[], body)) {
+            Err(GithubError::HttpError {
+                status: 301,
+                message,
+                ..
+            }) => assert_eq!(message, "Moved Permanently"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn not_found() {
+        let body =
+            r#"{"message": "Not Found", "documentation_url": "https://docs.github.com/rest"}"#;
+        match GithubError::from_response(&response(404, &[], body)) {
+            GithubError::NotFound {
+                message,
+                documentation_url,
+            } => {
+                assert_eq!(message, "Not Found");
+                assert_eq!(
+                    documentation_url.as_deref(),
+                    Some("https://docs.github.com/rest")
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validation_failed_with_object_and_string_errors() {
+        let body = r#"{
+            "message": "Validation Failed",
+            "errors": [
+                {"resource": "Label", "field": "name", "code": "already_exists"},
+                "Reviews may only be requested from collaborators."
+            ]
+        }"#;
+        match GithubError::from_response(&response(422, &[], body)) {
+            GithubError::ValidationFailed {
+                message, errors, ..
+            } => {
+                assert_eq!(message, "Validation Failed");
+                assert_eq!(
+                    errors,
+                    vec![
+                        FieldError {
+                            resource: Some("Label".into()),
+                            field: Some("name".into()),
+                            code: Some("already_exists".into()),
+                            message: None,
+                        },
+                        FieldError {
+                            message: Some(
+                                "Reviews may only be requested from collaborators.".into()
+                            ),
+                            ..Default::default()
+                        },
+                    ]
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn primary_rate_limit() {
+        let headers = [
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", "1700000000"),
+        ];
+        let body = r#"{"message": "API rate limit exceeded for installation ID 1."}"#;
+        match GithubError::from_response(&response(403, &headers, body)) {
+            GithubError::RateLimited { reset, .. } => {
+                assert_eq!(reset.unwrap().timestamp(), 1_700_000_000)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn secondary_rate_limit() {
+        let body = r#"{"message": "You have exceeded a secondary rate limit."}"#;
+        let before = Utc::now();
+        match GithubError::from_response(&response(403, &[("Retry-After", "60")], body)) {
+            GithubError::RateLimited { reset, .. } => {
+                let wait = reset.unwrap() - before;
+                assert!(wait >= chrono::Duration::seconds(60));
+                assert!(wait < chrono::Duration::seconds(70));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // Without any headers, the message is all there is to go on
+        match GithubError::from_response(&response(403, &[], body)) {
+            GithubError::RateLimited { reset: None, .. } => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match GithubError::from_response(&response(429, &[], "")) {
+            GithubError::RateLimited { reset: None, .. } => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn forbidden_and_unauthorized() {
+        let body = r#"{"message": "Resource not accessible by integration"}"#;
+        match GithubError::from_response(&response(403, &[("X-RateLimit-Remaining", "10")], body)) {
+            GithubError::Forbidden { message, .. } => {
+                assert_eq!(message, "Resource not accessible by integration")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match GithubError::from_response(&response(401, &[], r#"{"message": "Bad credentials"}"#)) {
+            GithubError::Unauthorized { message, .. } => assert_eq!(message, "Bad credentials"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn server_and_other_errors() {
+        match GithubError::from_response(&response(502, &[], "<html>Bad gateway</html>")) {
+            GithubError::ServerError { status, message } => {
+                assert_eq!(status, 502);
+                assert_eq!(message, "<html>Bad gateway</html>");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match GithubError::from_response(&response(
+            409,
+            &[],
+            r#"{"message": "Git Repository is empty."}"#,
+        )) {
+            GithubError::HttpError {
+                status: 409,
+                message,
+                ..
+            } => assert_eq!(message, "Git Repository is empty."),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
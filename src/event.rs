@@ -0,0 +1,427 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::checks::{CheckRun, CheckSuite};
+use crate::pull_request::State;
+use crate::{
+    Account, Installation, InstallationRepository, Issue, IssueComment, PullRequest, Repo, Review,
+};
+
+/// A webhook payload, keyed by the `X-GitHub-Event` header.
+///
+/// Events this crate doesn't model yet come through as `Unknown` so that
+/// dispatch code keeps working when GitHub adds new ones. Payloads of a known
+/// event that don't fit its type, for instance because GitHub dropped a
+/// field, come through as `Unparsed` instead, along with why.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    CheckRun(Box<CheckRunEvent>),
+    CheckSuite(Box<CheckSuiteEvent>),
+    Installation(Box<InstallationEvent>),
+    InstallationRepositories(Box<InstallationRepositoriesEvent>),
+    IssueComment(Box<IssueCommentEvent>),
+    Ping(Box<PingEvent>),
+    PullRequest(Box<PullRequestEvent>),
+    PullRequestReview(Box<PullRequestReviewEvent>),
+    Push(Box<PushEvent>),
+    Status(Box<StatusEvent>),
+    Unknown(serde_json::Value),
+    Unparsed {
+        /// The `X-GitHub-Event` name, e.g. `pull_request`.
+        name: String,
+        /// Why the payload didn't fit the event's type.
+        error: String,
+        payload: serde_json::Value,
+    },
+}
+
+impl Event {
+    /// Only fails if `payload` isn't JSON at all.
+    pub fn from_slice<T: AsRef<str>>(event: T, payload: &[u8]) -> Result<Event, Error> {
+        let event = event.as_ref();
+        let typed = match event {
+            "check_run" => serde_json::from_slice(payload).map(Event::CheckRun),
+            "check_suite" => serde_json::from_slice(payload).map(Event::CheckSuite),
+            "installation" => serde_json::from_slice(payload).map(Event::Installation),
+            "installation_repositories" => {
+                serde_json::from_slice(payload).map(Event::InstallationRepositories)
+            }
+            "issue_comment" => serde_json::from_slice(payload).map(Event::IssueComment),
+            "ping" => serde_json::from_slice(payload).map(Event::Ping),
+            "pull_request" => serde_json::from_slice(payload).map(Event::PullRequest),
+            "pull_request_review" => serde_json::from_slice(payload).map(Event::PullRequestReview),
+            "push" => serde_json::from_slice(payload).map(Event::Push),
+            "status" => serde_json::from_slice(payload).map(Event::Status),
+            other => {
+                debug!("Unrecognised webhook event: {}", other);
+                return Ok(Event::Unknown(serde_json::from_slice(payload)?));
+            }
+        };
+        match typed {
+            Ok(typed) => Ok(typed),
+            Err(e) => {
+                warn!("Couldn't parse {} webhook payload: {}", event, e);
+                Ok(Event::Unparsed {
+                    name: event.to_string(),
+                    error: e.to_string(),
+                    payload: serde_json::from_slice(payload)?,
+                })
+            }
+        }
+    }
+}
+
+/// The installation an event was delivered for. Most events only carry the
+/// id; use `App::installation` to get a usable `AppInstallation`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InstallationRef {
+    pub id: usize,
+    pub node_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckRunEvent {
+    pub action: String,
+    pub check_run: CheckRun,
+    pub requested_action: Option<RequestedAction>,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RequestedAction {
+    pub identifier: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckSuiteEvent {
+    pub action: String,
+    pub check_suite: CheckSuite,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InstallationEvent {
+    pub action: String,
+    pub installation: Installation,
+    pub repositories: Option<Vec<InstallationRepository>>,
+    pub requester: Option<Account>,
+    pub sender: Account,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InstallationRepositoriesEvent {
+    pub action: String,
+    pub installation: Installation,
+    pub repository_selection: String,
+    pub repositories_added: Vec<InstallationRepository>,
+    pub repositories_removed: Vec<InstallationRepository>,
+    pub requester: Option<Account>,
+    pub sender: Account,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssueCommentEvent {
+    pub action: String,
+    pub issue: Issue,
+    pub comment: IssueComment,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PingEvent {
+    pub zen: String,
+    pub hook_id: usize,
+    pub hook: serde_json::Value,
+    pub repository: Option<Repo>,
+    pub sender: Option<Account>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PullRequestEvent {
+    pub action: String,
+    pub number: usize,
+    pub pull_request: PullRequest,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PullRequestReviewEvent {
+    pub action: String,
+    pub review: Review,
+    pub pull_request: PullRequest,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PushEvent {
+    #[serde(rename = "ref")]
+    pub push_ref: String,
+    pub before: String,
+    pub after: String,
+    pub created: bool,
+    pub deleted: bool,
+    pub forced: bool,
+    pub base_ref: Option<String>,
+    pub compare: String,
+    pub commits: Vec<PushCommit>,
+    pub head_commit: Option<PushCommit>,
+    pub repository: Repo,
+    pub pusher: CommitAuthor,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PushCommit {
+    pub id: String,
+    pub tree_id: String,
+    pub distinct: bool,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    pub url: String,
+    pub author: CommitAuthor,
+    pub committer: CommitAuthor,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: Option<String>,
+    pub username: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StatusEvent {
+    pub id: usize,
+    pub sha: String,
+    pub name: String,
+    pub target_url: Option<String>,
+    pub context: String,
+    pub description: Option<String>,
+    pub state: State,
+    pub commit: serde_json::Value,
+    pub branches: Vec<StatusBranch>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub repository: Repo,
+    pub sender: Account,
+    pub installation: Option<InstallationRef>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StatusBranch {
+    pub name: String,
+    pub commit: StatusBranchCommit,
+    pub protected: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StatusBranchCommit {
+    pub sha: String,
+    pub url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::Conclusion;
+    use crate::review::ReviewState;
+
+    macro_rules! fixture {
+        ($name:expr) => {
+            Event::from_slice(
+                $name,
+                include_bytes!(concat!("../tests/fixtures/events/", $name, ".json")),
+            )
+            .unwrap()
+        };
+    }
+
+    #[test]
+    fn check_run() {
+        match fixture!("check_run") {
+            Event::CheckRun(event) => {
+                assert_eq!(event.action, "requested_action");
+                assert_eq!(event.check_run.name, "Octocoders-linter");
+                assert_eq!(event.check_run.conclusion, Some(Conclusion::ActionRequired));
+                assert_eq!(event.requested_action.unwrap().identifier, "fix");
+                assert_eq!(event.installation.unwrap().id, 2311213);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_suite() {
+        match fixture!("check_suite") {
+            Event::CheckSuite(event) => {
+                assert_eq!(event.action, "completed");
+                assert_eq!(event.check_suite.head_branch.as_deref(), Some("changes"));
+                assert_eq!(event.check_suite.latest_check_runs_count, Some(1));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn installation() {
+        match fixture!("installation") {
+            Event::Installation(event) => {
+                assert_eq!(event.action, "created");
+                assert_eq!(event.installation.account.login, "Octocoders");
+                let permissions = &event.installation.permissions;
+                assert_eq!(permissions.pull_requests, None);
+                assert_eq!(permissions.metadata.as_deref(), Some("read"));
+                assert_eq!(permissions.other["checks"], "write");
+                assert_eq!(event.repositories.unwrap()[0].name, "Hello-World");
+                assert!(event.requester.is_none());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn installation_repositories() {
+        match fixture!("installation_repositories") {
+            Event::InstallationRepositories(event) => {
+                assert_eq!(event.action, "added");
+                assert_eq!(event.repository_selection, "selected");
+                assert_eq!(event.repositories_added[0].full_name, "Codertocat/Space");
+                assert!(event.repositories_removed.is_empty());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn issue_comment() {
+        match fixture!("issue_comment") {
+            Event::IssueComment(event) => {
+                assert_eq!(event.action, "created");
+                assert_eq!(event.issue.number, 1);
+                assert!(event.issue.pull_request.is_none());
+                assert_eq!(event.issue.labels[0].name, "bug");
+                assert!(event.comment.body.starts_with("You are totally right!"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ping() {
+        match fixture!("ping") {
+            Event::Ping(event) => {
+                assert_eq!(event.zen, "Keep it logically awesome.");
+                assert_eq!(event.hook_id, 30);
+                assert!(event.repository.is_none());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pull_request_from_deleted_fork() {
+        match fixture!("pull_request") {
+            Event::PullRequest(event) => {
+                assert_eq!(event.action, "closed");
+                assert_eq!(event.number, 2);
+                let pull_request = event.pull_request;
+                assert!(pull_request.head.repo.is_none());
+                assert_eq!(pull_request.head.pr_ref, "changes");
+                assert_eq!(pull_request.base.repo.full_name, "Codertocat/Hello-World");
+                assert_eq!(pull_request.requested_teams[0].description, None);
+                assert_eq!(pull_request.milestone.unwrap().title, "v1.0");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pull_request_review() {
+        match fixture!("pull_request_review") {
+            Event::PullRequestReview(event) => {
+                assert_eq!(event.action, "submitted");
+                assert_eq!(event.review.state, ReviewState::Commented);
+                assert_eq!(event.review.body, None);
+                let head_repo = event.pull_request.head.repo.unwrap();
+                assert_eq!(head_repo.full_name, "Codertocat/Hello-World");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn push() {
+        match fixture!("push") {
+            Event::Push(event) => {
+                assert_eq!(event.push_ref, "refs/heads/master");
+                assert_eq!(event.commits.len(), 1);
+                assert_eq!(event.commits[0].modified, vec!["README.md"]);
+                assert_eq!(event.pusher.name, "Codertocat");
+                // Push payloads use Unix timestamps for these
+                assert_eq!(event.repository.pushed_at.timestamp(), 1557933657);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn status() {
+        match fixture!("status") {
+            Event::Status(event) => {
+                assert_eq!(event.state, State::Success);
+                assert_eq!(event.context, "default");
+                assert_eq!(event.target_url, None);
+                assert_eq!(event.branches[0].name, "master");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unrecognised_events_are_unknown() {
+        match Event::from_slice("star", br#"{"action": "created"}"#).unwrap() {
+            Event::Unknown(value) => assert_eq!(value["action"], "created"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payloads_that_dont_fit_their_type_are_unparsed() {
+        let payload = br#"{"action": "opened", "number": "not a number"}"#;
+        match Event::from_slice("pull_request", payload).unwrap() {
+            Event::Unparsed {
+                name,
+                error,
+                payload,
+            } => {
+                assert_eq!(name, "pull_request");
+                assert!(error.contains("invalid type"), "{}", error);
+                assert_eq!(payload["action"], "opened");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        assert!(Event::from_slice("pull_request", b"{").is_err());
+        assert!(Event::from_slice("star", b"").is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::pagination::encode;
+use crate::{AppInstallation, GitCommit, Repo, Signature};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Blob {
+    pub sha: String,
+    pub url: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum FileMode {
+    #[serde(rename = "100644")]
+    File,
+    #[serde(rename = "100755")]
+    Executable,
+    #[serde(rename = "040000")]
+    Subdirectory,
+    #[serde(rename = "160000")]
+    Submodule,
+    #[serde(rename = "120000")]
+    Symlink,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tree {
+    pub sha: String,
+    pub url: String,
+    pub tree: Vec<TreeEntry>,
+    /// Set when GitHub left out entries because the tree was too large.
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TreeEntry {
+    pub path: String,
+    pub mode: FileMode,
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+    pub sha: String,
+    pub size: Option<usize>,
+    pub url: Option<String>,
+}
+
+/// An entry for `Repo::create_tree`. `sha` points at an existing object;
+/// `None` deletes `path` from the base tree.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NewTreeEntry {
+    pub path: String,
+    pub mode: FileMode,
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+    pub sha: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewCommit {
+    pub message: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    /// Defaults to the app's bot account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<NewSignature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer: Option<NewSignature>,
+}
+
+/// An author or committer for a new commit. `date` defaults to now.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewSignature {
+    pub name: String,
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitRef {
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub node_id: String,
+    pub url: String,
+    pub object: GitObject,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitObject {
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+    pub sha: String,
+    pub url: String,
+}
+
+/// One change in `Repo::commit_files`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileChange {
+    Write {
+        path: String,
+        content: Vec<u8>,
+        mode: FileMode,
+    },
+    Delete {
+        path: String,
+    },
+}
+
+#[derive(Serialize)]
+struct NewTree<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_tree: Option<&'a str>,
+    tree: &'a [NewTreeEntry],
+}
+
+impl From<Signature> for NewSignature {
+    fn from(signature: Signature) -> NewSignature {
+        NewSignature {
+            name: signature.name,
+            email: signature.email,
+            date: Some(signature.date),
+        }
+    }
+}
+
+impl FileChange {
+    pub fn write<P: Into<String>, C: Into<Vec<u8>>>(path: P, content: C) -> FileChange {
+        FileChange::Write {
+            path: path.into(),
+            content: content.into(),
+            mode: FileMode::File,
+        }
+    }
+
+    pub fn delete<P: Into<String>>(path: P) -> FileChange {
+        FileChange::Delete { path: path.into() }
+    }
+}
+
+impl Repo {
+    pub fn create_blob(
+        &self,
+        installation: &AppInstallation,
+        content: &[u8],
+    ) -> Result<Blob, Error> {
+        installation.post_json(
+            self.git_url(installation, "blobs"),
+            &serde_json::json!({
+                "content": STANDARD.encode(content),
+                "encoding": "base64",
+            }),
+        )
+    }
+
+    /// Fetches a tree; `recursive` includes every subtree as well.
+    pub fn tree<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        sha: T,
+        recursive: bool,
+    ) -> Result<Tree, Error> {
+        let mut url = self.git_url(installation, format!("trees/{}", encode(sha.as_ref())));
+        if recursive {
+            url.push_str("?recursive=1");
+        }
+        installation.get_json(url)
+    }
+
+    /// Creates a tree from `entries`. With a `base_tree`, entries are applied
+    /// on top of it; otherwise the new tree holds only `entries`.
+    pub fn create_tree(
+        &self,
+        installation: &AppInstallation,
+        base_tree: Option<&str>,
+        entries: &[NewTreeEntry],
+    ) -> Result<Tree, Error> {
+        installation.post_json(
+            self.git_url(installation, "trees"),
+            &NewTree {
+                base_tree,
+                tree: entries,
+            },
+        )
+    }
+
+    pub fn git_commit<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        sha: T,
+    ) -> Result<GitCommit, Error> {
+        installation
+            .get_json(self.git_url(installation, format!("commits/{}", encode(sha.as_ref()))))
+    }
+
+    pub fn create_commit(
+        &self,
+        installation: &AppInstallation,
+        commit: &NewCommit,
+    ) -> Result<GitCommit, Error> {
+        installation.post_json(self.git_url(installation, "commits"), commit)
+    }
+
+    /// `git_ref` is given without the `refs/` prefix, e.g. `heads/main` or
+    /// `tags/v1.0`.
+    pub fn git_ref<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<GitRef, Error> {
+        installation.get_json(self.git_url(installation, format!("ref/{}", git_ref.as_ref())))
+    }
+
+    /// `git_ref` is the full name, e.g. `refs/heads/my-branch`.
+    pub fn create_ref<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T1,
+        sha: T2,
+    ) -> Result<GitRef, Error> {
+        installation.post_json(
+            self.git_url(installation, "refs"),
+            &serde_json::json!({ "ref": git_ref.as_ref(), "sha": sha.as_ref() }),
+        )
+    }
+
+    /// Points `git_ref` (e.g. `heads/main`) at `sha`. Unless `force` is set
+    /// this only succeeds as a fast-forward.
+    pub fn update_ref<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T1,
+        sha: T2,
+        force: bool,
+    ) -> Result<GitRef, Error> {
+        installation.patch_json(
+            self.git_url(installation, format!("refs/{}", git_ref.as_ref())),
+            &serde_json::json!({ "sha": sha.as_ref(), "force": force }),
+        )
+    }
+
+    pub fn delete_ref<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<(), Error> {
+        installation.delete(self.git_url(installation, format!("refs/{}", git_ref.as_ref())))
+    }
+
+    /// Commits `changes` on top of `branch` in a single commit and moves the
+    /// branch to it, without needing a checkout. The branch update is a
+    /// fast-forward, so this fails rather than losing work if someone else
+    /// pushed to the branch in the meantime.
+    pub fn commit_files<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        branch: T1,
+        message: T2,
+        changes: &[FileChange],
+    ) -> Result<GitCommit, Error> {
+        let head_ref = format!("heads/{}", branch.as_ref());
+        let head = self.git_ref(installation, &head_ref)?;
+        let parent = self.git_commit(installation, &head.object.sha)?;
+        let mut entries = Vec::with_capacity(changes.len());
+        for change in changes {
+            entries.push(match change {
+                FileChange::Write {
+                    path,
+                    content,
+                    mode,
+                } => NewTreeEntry {
+                    path: path.clone(),
+                    mode: *mode,
+                    object_type: ObjectType::Blob,
+                    sha: Some(self.create_blob(installation, content)?.sha),
+                },
+                FileChange::Delete { path } => NewTreeEntry {
+                    path: path.clone(),
+                    mode: FileMode::File,
+                    object_type: ObjectType::Blob,
+                    sha: None,
+                },
+            });
+        }
+        let tree = self.create_tree(installation, Some(&parent.tree.sha), &entries)?;
+        let commit = self.create_commit(
+            installation,
+            &NewCommit {
+                message: message.as_ref().to_string(),
+                tree: tree.sha,
+                parents: vec![parent.sha],
+                ..Default::default()
+            },
+        )?;
+        debug!(
+            "Moving {} in {} to {}",
+            head_ref, self.full_name, commit.sha
+        );
+        self.update_ref(installation, &head_ref, &commit.sha, false)?;
+        Ok(commit)
+    }
+
+    fn git_url<T: AsRef<str>>(&self, installation: &AppInstallation, path: T) -> String {
+        installation.api(format!("repos/{}/git/{}", self.full_name, path.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::testing;
+    use crate::transport::Method;
+
+    const GIT: &str = "https://api.github.com/repos/Codertocat/Hello-World/git";
+
+    fn git_commit(sha: &str, tree: &str, parent: &str) -> Value {
+        let signature = json!({
+            "name": "my-app[bot]",
+            "email": "31016+my-app[bot]@users.noreply.github.com",
+            "date": "2019-05-15T15:20:30Z",
+        });
+        json!({
+            "sha": sha,
+            "node_id": "MDY6Q29tbWl0MTg2ODUzMDAyOjdiOGU=",
+            "url": format!("{}/commits/{}", GIT, sha),
+            "html_url": null,
+            "message": "Update files",
+            "author": signature,
+            "committer": signature,
+            "tree": {"sha": tree, "url": format!("{}/trees/{}", GIT, tree)},
+            "parents": [{"sha": parent, "url": format!("{}/commits/{}", GIT, parent)}],
+        })
+    }
+
+    #[test]
+    fn commit_files_fast_forwards_the_branch() {
+        let transport = testing::github(|request| {
+            let path = request.url.trim_start_matches(GIT);
+            let body = match (request.method, path) {
+                (Method::Get, "/ref/heads/main") => json!({
+                    "ref": "refs/heads/main",
+                    "node_id": "MDM6UmVmcmVmcy9oZWFkcy9tYWlu",
+                    "url": format!("{}/refs/heads/main", GIT),
+                    "object": {"type": "commit", "sha": "c0", "url": format!("{}/commits/c0", GIT)},
+                }),
+                (Method::Get, "/commits/c0") => git_commit("c0", "t0", "cp"),
+                (Method::Post, "/blobs") => {
+                    json!({"sha": "b1", "url": format!("{}/blobs/b1", GIT)})
+                }
+                (Method::Post, "/trees") => json!({
+                    "sha": "t1",
+                    "url": format!("{}/trees/t1", GIT),
+                    "tree": [],
+                    "truncated": false,
+                }),
+                (Method::Post, "/commits") => git_commit("c1", "t1", "c0"),
+                (Method::Patch, "/refs/heads/main") => json!({
+                    "ref": "refs/heads/main",
+                    "node_id": "MDM6UmVmcmVmcy9oZWFkcy9tYWlu",
+                    "url": format!("{}/refs/heads/main", GIT),
+                    "object": {"type": "commit", "sha": "c1", "url": format!("{}/commits/c1", GIT)},
+                }),
+                _ => panic!("unexpected {:?} {}", request.method, request.url),
+            };
+            testing::response(200, &[], &body.to_string())
+        });
+        let installation = testing::installation(&transport);
+        let commit = testing::repo()
+            .commit_files(
+                &installation,
+                "main",
+                "Update files",
+                &[
+                    FileChange::write("README.md", "Hello"),
+                    FileChange::delete("old.txt"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(commit.sha, "c1");
+
+        let requests: Vec<(Method, String, Value)> = transport
+            .requests()
+            .into_iter()
+            .skip(1)
+            .map(|request| {
+                let body = request
+                    .body
+                    .map(|body| serde_json::from_slice(&body).unwrap())
+                    .unwrap_or_default();
+                (request.method, request.url.replace(GIT, ""), body)
+            })
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                (Method::Get, "/ref/heads/main".into(), Value::Null),
+                (Method::Get, "/commits/c0".into(), Value::Null),
+                (
+                    Method::Post,
+                    "/blobs".into(),
+                    json!({"content": "SGVsbG8=", "encoding": "base64"}),
+                ),
+                (
+                    Method::Post,
+                    "/trees".into(),
+                    json!({
+                        "base_tree": "t0",
+                        "tree": [
+                            {"path": "README.md", "mode": "100644", "type": "blob", "sha": "b1"},
+                            {"path": "old.txt", "mode": "100644", "type": "blob", "sha": null},
+                        ],
+                    }),
+                ),
+                (
+                    Method::Post,
+                    "/commits".into(),
+                    json!({"message": "Update files", "tree": "t1", "parents": ["c0"]}),
+                ),
+                (
+                    Method::Patch,
+                    "/refs/heads/main".into(),
+                    json!({"sha": "c1", "force": false}),
+                ),
+            ]
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use chrono::prelude::*;
 
 use crate::Account;
@@ -20,8 +22,24 @@ pub struct Installation {
     pub single_file_name: Option<String>,
 }
 
+/// The abbreviated repository GitHub sends in installation events and
+/// installation tokens.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InstallationRepository {
+    pub id: usize,
+    pub node_id: String,
+    pub name: String,
+    pub full_name: String,
+    pub private: bool,
+}
+
+/// What the installation was granted, e.g. `read` or `write`. Permissions
+/// the app didn't ask for are left out.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Permissions {
-    pub pull_requests: String, // Enum
-    pub metadata: String,      // Enum
+    pub pull_requests: Option<String>, // Enum
+    pub metadata: Option<String>,      // Enum
+    /// Every other permission, keyed by name, e.g. `checks` => `write`.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, String>,
 }
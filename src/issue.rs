@@ -0,0 +1,432 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::pagination::{encode, with_query};
+use crate::{
+    Account, AppInstallation, GithubError, Label, Milestone, Paginated, PullRequest, Repo,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueState {
+    Open,
+    Closed,
+}
+
+/// Why an issue was closed, or `Reopened` once it has been reopened.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateReason {
+    Completed,
+    NotPlanned,
+    Duplicate,
+    Reopened,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum LockReason {
+    #[serde(rename = "off-topic")]
+    OffTopic,
+    #[serde(rename = "too heated")]
+    TooHeated,
+    #[serde(rename = "resolved")]
+    Resolved,
+    #[serde(rename = "spam")]
+    Spam,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Issue {
+    pub id: usize,
+    pub node_id: String,
+    pub number: usize,
+    pub url: String,
+    pub repository_url: String,
+    pub labels_url: String,
+    pub comments_url: String,
+    pub events_url: String,
+    pub html_url: String,
+    pub state: IssueState,
+    pub state_reason: Option<StateReason>,
+    pub title: String,
+    pub body: Option<String>,
+    pub user: Option<Account>,
+    pub labels: Vec<Label>,
+    pub assignee: Option<Account>,
+    pub assignees: Vec<Account>,
+    pub milestone: Option<Milestone>,
+    pub locked: bool,
+    pub active_lock_reason: Option<LockReason>,
+    pub comments: usize,
+    /// Set when the issue is really a pull request.
+    pub pull_request: Option<IssuePullRequest>,
+    pub author_association: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssuePullRequest {
+    pub url: String,
+    pub html_url: String,
+    pub diff_url: String,
+    pub patch_url: String,
+    pub merged_at: Option<DateTime<Utc>>,
+}
+
+/// Narrows down `Repo::issues`. The default lists open and closed issues.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IssueFilter {
+    pub state: Option<IssueState>,
+    /// Only issues carrying every one of these labels.
+    pub labels: Vec<String>,
+    /// A login, `none` for unassigned issues or `*` for assigned ones.
+    pub assignee: Option<String>,
+    /// Only issues updated at or after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewIssue {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// The milestone's number, not its id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<usize>,
+}
+
+/// Changes to make to an issue; fields left as `None` are not touched.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct IssueUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<IssueState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_reason: Option<StateReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignees: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<usize>,
+}
+
+impl IssueFilter {
+    /// Adds the filter's query parameters to `url`.
+    fn apply(&self, url: &str) -> String {
+        let state = match self.state {
+            Some(IssueState::Open) => "open",
+            Some(IssueState::Closed) => "closed",
+            None => "all",
+        };
+        let mut url = with_query(url, "state", state);
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self.labels.iter().map(|label| encode(label)).collect();
+            url = with_query(&url, "labels", &labels.join(","));
+        }
+        if let Some(ref assignee) = self.assignee {
+            url = with_query(&url, "assignee", &encode(assignee));
+        }
+        if let Some(since) = self.since {
+            let since = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+            url = with_query(&url, "since", &encode(&since));
+        }
+        url
+    }
+}
+
+impl Issue {
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+}
+
+impl Repo {
+    /// Note that GitHub counts pull requests as issues, so they are listed
+    /// here too; see `Issue::is_pull_request`.
+    pub fn issues(
+        &self,
+        installation: &AppInstallation,
+        filter: &IssueFilter,
+    ) -> Result<Vec<Issue>, Error> {
+        self.iter_issues(installation, filter).collect()
+    }
+
+    pub fn iter_issues<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        filter: &IssueFilter,
+    ) -> Paginated<'a, Issue> {
+        installation
+            .paginate(filter.apply(&installation.api(format!("repos/{}/issues", self.full_name))))
+    }
+
+    pub fn issue(&self, installation: &AppInstallation, number: usize) -> Result<Issue, Error> {
+        installation.get_json(self.issues_api_url(installation, number.to_string()))
+    }
+
+    pub fn create_issue(
+        &self,
+        installation: &AppInstallation,
+        issue: &NewIssue,
+    ) -> Result<Issue, Error> {
+        installation.post_json(
+            installation.api(format!("repos/{}/issues", self.full_name)),
+            issue,
+        )
+    }
+
+    pub fn edit_issue(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        update: &IssueUpdate,
+    ) -> Result<Issue, Error> {
+        installation.patch_json(
+            self.issues_api_url(installation, number.to_string()),
+            update,
+        )
+    }
+
+    pub fn close_issue(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        reason: StateReason,
+    ) -> Result<Issue, Error> {
+        self.edit_issue(
+            installation,
+            number,
+            &IssueUpdate {
+                state: Some(IssueState::Closed),
+                state_reason: Some(reason),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn reopen_issue(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+    ) -> Result<Issue, Error> {
+        self.edit_issue(
+            installation,
+            number,
+            &IssueUpdate {
+                state: Some(IssueState::Open),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn lock_issue(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        reason: Option<LockReason>,
+    ) -> Result<(), Error> {
+        let body = match reason {
+            Some(reason) => serde_json::json!({ "lock_reason": reason }),
+            None => serde_json::json!({}),
+        };
+        installation.put_json(
+            self.issues_api_url(installation, format!("{}/lock", number)),
+            &body,
+        )
+    }
+
+    pub fn unlock_issue(&self, installation: &AppInstallation, number: usize) -> Result<(), Error> {
+        installation.delete(self.issues_api_url(installation, format!("{}/lock", number)))
+    }
+
+    /// Adds assignees to an issue or pull request. GitHub silently ignores
+    /// logins that can't be assigned, so each one is checked with
+    /// `is_assignable` first, and if any fail nobody is assigned and
+    /// `GithubError::NotAssignable` lists them.
+    pub fn add_assignees<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        logins: &[T],
+    ) -> Result<Issue, Error> {
+        let mut rejected = vec![];
+        for login in logins {
+            if !self.is_assignable(installation, login)? {
+                rejected.push(login.as_ref().to_string());
+            }
+        }
+        if !rejected.is_empty() {
+            return Err(GithubError::NotAssignable { logins: rejected }.into());
+        }
+        installation.post_json(
+            self.issues_api_url(installation, format!("{}/assignees", number)),
+            &Assignees::new(logins),
+        )
+    }
+
+    pub fn remove_assignees<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        logins: &[T],
+    ) -> Result<Issue, Error> {
+        installation.delete_json(
+            self.issues_api_url(installation, format!("{}/assignees", number)),
+            &Assignees::new(logins),
+        )
+    }
+
+    /// Whether `login` may be assigned issues and pull requests here.
+    pub fn is_assignable<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        login: T,
+    ) -> Result<bool, Error> {
+        let url = installation.api(format!(
+            "repos/{}/assignees/{}",
+            self.full_name,
+            encode(login.as_ref())
+        ));
+        match installation.get_json::<_, ()>(url) {
+            Ok(()) => Ok(true),
+            Err(e) => match e.downcast::<GithubError>() {
+                Ok(GithubError::NotFound { .. }) => Ok(false),
+                Ok(e) => Err(e.into()),
+                Err(e) => Err(e),
+            },
+        }
+    }
+}
+
+impl PullRequest {
+    pub fn add_assignees<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        logins: &[T],
+    ) -> Result<Issue, Error> {
+        self.base
+            .repo
+            .add_assignees(installation, self.number, logins)
+    }
+
+    pub fn remove_assignees<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        logins: &[T],
+    ) -> Result<Issue, Error> {
+        self.base
+            .repo
+            .remove_assignees(installation, self.number, logins)
+    }
+}
+
+#[derive(Serialize)]
+struct Assignees<'a> {
+    assignees: Vec<&'a str>,
+}
+
+impl<'a> Assignees<'a> {
+    fn new<T: AsRef<str>>(logins: &'a [T]) -> Assignees<'a> {
+        Assignees {
+            assignees: logins.iter().map(AsRef::as_ref).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::testing;
+    use crate::transport::Method;
+
+    /// Hello-World#1, as the issue_comment fixture describes it.
+    fn issue() -> String {
+        let event: Value = serde_json::from_slice(include_bytes!(
+            "../tests/fixtures/events/issue_comment.json"
+        ))
+        .unwrap();
+        event["issue"].to_string()
+    }
+
+    #[test]
+    fn filter_defaults_to_all_issues() {
+        assert_eq!(
+            IssueFilter::default().apply("https://api.github.com/repos/o/r/issues"),
+            "https://api.github.com/repos/o/r/issues?state=all"
+        );
+    }
+
+    #[test]
+    fn filter_encodes_every_parameter() {
+        let filter = IssueFilter {
+            state: Some(IssueState::Open),
+            labels: vec!["bug".into(), "good first issue".into(), "a,b".into()],
+            assignee: Some("*".into()),
+            since: Some(Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap()),
+        };
+        assert_eq!(
+            filter.apply("https://api.github.com/repos/o/r/issues"),
+            "https://api.github.com/repos/o/r/issues?state=open\
+             &labels=bug,good%20first%20issue,a%2Cb\
+             &assignee=%2A\
+             &since=2024-05-01T12%3A30%3A00Z"
+        );
+    }
+
+    fn github() -> testing::MockTransport {
+        testing::github(|request| match request.url.as_str() {
+            "https://api.github.com/repos/Codertocat/Hello-World/assignees/Codertocat" => {
+                testing::response(204, &[], "")
+            }
+            "https://api.github.com/repos/Codertocat/Hello-World/assignees/ghost" => {
+                testing::response(404, &[], r#"{"message": "Not Found"}"#)
+            }
+            "https://api.github.com/repos/Codertocat/Hello-World/issues/1/assignees" => {
+                testing::response(201, &[], &issue())
+            }
+            url => panic!("unexpected request for {}", url),
+        })
+    }
+
+    #[test]
+    fn add_assignees_checks_assignability() {
+        let transport = github();
+        let installation = testing::installation(&transport);
+        let issue = testing::repo()
+            .add_assignees(&installation, 1, &["Codertocat"])
+            .unwrap();
+        assert_eq!(issue.number, 1);
+        let assign = transport.requests().pop().unwrap();
+        assert_eq!(assign.method, Method::Post);
+        assert_eq!(assign.body.unwrap(), br#"{"assignees":["Codertocat"]}"#);
+    }
+
+    #[test]
+    fn add_assignees_rejects_unassignable_logins() {
+        let transport = github();
+        let installation = testing::installation(&transport);
+        let error = testing::repo()
+            .add_assignees(&installation, 1, &["ghost", "Codertocat"])
+            .unwrap_err();
+        match error.downcast::<GithubError>().unwrap() {
+            GithubError::NotAssignable { logins } => assert_eq!(logins, vec!["ghost"]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(transport
+            .requests()
+            .iter()
+            .all(|request| !request.url.ends_with("/issues/1/assignees")));
+    }
+}
@@ -33,8 +33,8 @@ impl JsonWebToken {
         let jwt = JsonWebToken {
             expires: Arc::new(RwLock::new(expires_time)),
             token: Arc::new(RwLock::new(token)),
-            private_key: private_key,
-            application_id: application_id,
+            private_key,
+            application_id,
         };
 
         Ok(jwt)
@@ -74,7 +74,7 @@ impl JsonWebToken {
     }
 
     fn generate_token(
-        private_key: &Vec<u8>,
+        private_key: &[u8],
         application_id: &str,
     ) -> Result<(String, DateTime<Utc>), Error> {
         let start = Utc::now();
@@ -82,7 +82,7 @@ impl JsonWebToken {
         let exp = since_the_epoch + (10 * 60);
         let my_claims = Claims {
             iat: since_the_epoch,
-            exp: exp,
+            exp,
             iss: application_id,
         };
         let expires_time = start + chrono::Duration::seconds(10 * 60);
@@ -93,7 +93,7 @@ impl JsonWebToken {
         let token = encode(
             &Header::new(Algorithm::RS256),
             &my_claims,
-            &EncodingKey::from_rsa_pem(&private_key)?,
+            &EncodingKey::from_rsa_pem(private_key)?,
         )?; // &private_key)?;
         debug!("Built token: {:#?}", token);
         Ok((token, expires_time))
@@ -0,0 +1,410 @@
+use failure::Error;
+
+use crate::pagination::encode;
+use crate::{AppInstallation, Paginated, PullRequest, Repo};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Label {
+    pub id: usize,
+    pub node_id: String,
+    pub url: String,
+    pub name: String,
+    /// Six hex digits, without a leading `#`.
+    pub color: String,
+    pub description: Option<String>,
+    pub default: bool,
+}
+
+/// A label to create, or the desired state of one in `Repo::sync_labels`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct NewLabel {
+    pub name: String,
+    pub color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LabelUpdate<'a> {
+    new_name: &'a str,
+    color: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Labels<'a> {
+    labels: Vec<&'a str>,
+}
+
+impl NewLabel {
+    pub fn new<T1: Into<String>, T2: Into<String>>(name: T1, color: T2) -> NewLabel {
+        NewLabel {
+            name: name.into(),
+            color: color.into(),
+            description: None,
+        }
+    }
+
+    fn color(&self) -> &str {
+        self.color.trim_start_matches('#')
+    }
+
+    fn matches(&self, label: &Label) -> bool {
+        label.name == self.name
+            && label.color.eq_ignore_ascii_case(self.color())
+            && (self.description.is_none() || label.description == self.description)
+    }
+}
+
+impl Repo {
+    pub fn labels(&self, installation: &AppInstallation) -> Result<Vec<Label>, Error> {
+        self.iter_labels(installation).collect()
+    }
+
+    pub fn iter_labels<'a>(&self, installation: &'a AppInstallation) -> Paginated<'a, Label> {
+        installation.paginate(installation.api(format!("repos/{}/labels", self.full_name)))
+    }
+
+    pub fn label<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<Label, Error> {
+        installation.get_json(self.labels_api_url(installation, name))
+    }
+
+    pub fn create_label(
+        &self,
+        installation: &AppInstallation,
+        label: &NewLabel,
+    ) -> Result<Label, Error> {
+        let label = NewLabel {
+            color: label.color().to_string(),
+            ..label.clone()
+        };
+        installation.post_json(
+            installation.api(format!("repos/{}/labels", self.full_name)),
+            &label,
+        )
+    }
+
+    /// Updates the label currently called `name`, which may be renamed.
+    pub fn update_label<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+        label: &NewLabel,
+    ) -> Result<Label, Error> {
+        installation.patch_json(
+            self.labels_api_url(installation, name),
+            &LabelUpdate {
+                new_name: &label.name,
+                color: label.color(),
+                description: label.description.as_deref(),
+            },
+        )
+    }
+
+    pub fn delete_label<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<(), Error> {
+        installation.delete(self.labels_api_url(installation, name))
+    }
+
+    /// Makes the repository's labels match `labels`, creating any that are
+    /// missing and updating those whose colour or description differ. Names
+    /// are matched case-insensitively, as GitHub does. With `prune`, labels
+    /// not in the list are deleted too.
+    pub fn sync_labels(
+        &self,
+        installation: &AppInstallation,
+        labels: &[NewLabel],
+        prune: bool,
+    ) -> Result<Vec<Label>, Error> {
+        let mut existing = self.labels(installation)?;
+        let mut synced = Vec::with_capacity(labels.len());
+        for wanted in labels {
+            let position = existing
+                .iter()
+                .position(|label| label.name.eq_ignore_ascii_case(&wanted.name));
+            match position.map(|index| existing.remove(index)) {
+                Some(ref label) if wanted.matches(label) => synced.push(label.clone()),
+                Some(label) => {
+                    debug!("Updating label {} in {}", label.name, self.full_name);
+                    synced.push(self.update_label(installation, &label.name, wanted)?);
+                }
+                None => {
+                    debug!("Creating label {} in {}", wanted.name, self.full_name);
+                    synced.push(self.create_label(installation, wanted)?);
+                }
+            }
+        }
+        if prune {
+            for label in existing {
+                debug!("Deleting label {} from {}", label.name, self.full_name);
+                self.delete_label(installation, &label.name)?;
+            }
+        }
+        Ok(synced)
+    }
+
+    pub fn issue_labels(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+    ) -> Result<Vec<Label>, Error> {
+        self.iter_issue_labels(installation, number).collect()
+    }
+
+    pub fn iter_issue_labels<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        number: usize,
+    ) -> Paginated<'a, Label> {
+        installation.paginate(self.issues_api_url(installation, format!("{}/labels", number)))
+    }
+
+    /// Adds labels to an issue or pull request, returning all of its labels.
+    pub fn add_labels<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        labels: &[T],
+    ) -> Result<Vec<Label>, Error> {
+        installation.post_json(
+            self.issues_api_url(installation, format!("{}/labels", number)),
+            &Labels::new(labels),
+        )
+    }
+
+    /// Replaces all labels on an issue or pull request.
+    pub fn replace_labels<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        labels: &[T],
+    ) -> Result<Vec<Label>, Error> {
+        installation.put_json(
+            self.issues_api_url(installation, format!("{}/labels", number)),
+            &Labels::new(labels),
+        )
+    }
+
+    pub fn remove_label<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        name: T,
+    ) -> Result<(), Error> {
+        installation.delete(self.issues_api_url(
+            installation,
+            format!("{}/labels/{}", number, encode(name.as_ref())),
+        ))
+    }
+
+    pub fn clear_labels(&self, installation: &AppInstallation, number: usize) -> Result<(), Error> {
+        installation.delete(self.issues_api_url(installation, format!("{}/labels", number)))
+    }
+
+    fn labels_api_url<T: AsRef<str>>(&self, installation: &AppInstallation, name: T) -> String {
+        installation.api(format!(
+            "repos/{}/labels/{}",
+            self.full_name,
+            encode(name.as_ref())
+        ))
+    }
+}
+
+impl PullRequest {
+    pub fn add_labels<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        labels: &[T],
+    ) -> Result<Vec<Label>, Error> {
+        self.base.repo.add_labels(installation, self.number, labels)
+    }
+
+    pub fn replace_labels<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        labels: &[T],
+    ) -> Result<Vec<Label>, Error> {
+        self.base
+            .repo
+            .replace_labels(installation, self.number, labels)
+    }
+
+    pub fn remove_label<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        name: T,
+    ) -> Result<(), Error> {
+        self.base.repo.remove_label(installation, self.number, name)
+    }
+}
+
+impl<'a> Labels<'a> {
+    fn new<T: AsRef<str>>(labels: &'a [T]) -> Labels<'a> {
+        Labels {
+            labels: labels.iter().map(AsRef::as_ref).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::testing::{self, MockTransport};
+    use crate::transport::Method;
+
+    const LABELS: &str = "https://api.github.com/repos/Codertocat/Hello-World/labels";
+
+    fn label(name: &str, color: &str, description: Option<&str>) -> Value {
+        json!({
+            "id": 208045946,
+            "node_id": "MDU6TGFiZWwyMDgwNDU5NDY=",
+            "url": format!("{}/{}", LABELS, encode(name)),
+            "name": name,
+            "color": color,
+            "description": description,
+            "default": false,
+        })
+    }
+
+    /// A repository with `existing` labels, that applies every change.
+    fn github(existing: Vec<Value>) -> MockTransport {
+        testing::github(move |request| {
+            let sent: Value = request
+                .body
+                .as_ref()
+                .map(|body| serde_json::from_slice(body).unwrap())
+                .unwrap_or_default();
+            let description = sent["description"].as_str();
+            match request.method {
+                Method::Get => {
+                    testing::response(200, &[], &Value::from(existing.clone()).to_string())
+                }
+                Method::Post => {
+                    let name = sent["name"].as_str().unwrap();
+                    let color = sent["color"].as_str().unwrap();
+                    testing::response(201, &[], &label(name, color, description).to_string())
+                }
+                Method::Patch => {
+                    let name = sent["new_name"].as_str().unwrap();
+                    let color = sent["color"].as_str().unwrap();
+                    testing::response(200, &[], &label(name, color, description).to_string())
+                }
+                Method::Delete => testing::response(204, &[], ""),
+                Method::Put => panic!("unexpected PUT {}", request.url),
+            }
+        })
+    }
+
+    /// The changes made, as `(method, url, body)`.
+    fn changes(transport: &MockTransport) -> Vec<(Method, String, Value)> {
+        transport
+            .requests()
+            .into_iter()
+            .filter(|request| request.method != Method::Get)
+            .filter(|request| !request.url.ends_with("/access_tokens"))
+            .map(|request| {
+                let body = request
+                    .body
+                    .map(|body| serde_json::from_slice(&body).unwrap())
+                    .unwrap_or_default();
+                (request.method, request.url, body)
+            })
+            .collect()
+    }
+
+    fn wanted() -> Vec<NewLabel> {
+        vec![
+            // Has a description upstream, but this doesn't care about it
+            NewLabel::new("bug", "#D73A4A"),
+            // Only the case of the name differs
+            NewLabel::new("enhancement", "a2eeef"),
+            NewLabel {
+                description: Some("Improvements or additions to documentation".into()),
+                ..NewLabel::new("documentation", "0075ca")
+            },
+            NewLabel::new("triage", "ededed"),
+        ]
+    }
+
+    fn existing() -> Vec<Value> {
+        vec![
+            label("bug", "d73a4a", Some("Something isn't working")),
+            label("Enhancement", "a2eeef", None),
+            label("documentation", "0075ca", None),
+            label("wontfix", "ffffff", None),
+        ]
+    }
+
+    #[test]
+    fn sync_labels_creates_and_updates() {
+        let transport = github(existing());
+        let installation = testing::installation(&transport);
+        let synced = testing::repo()
+            .sync_labels(&installation, &wanted(), false)
+            .unwrap();
+        let names: Vec<&str> = synced.iter().map(|label| label.name.as_str()).collect();
+        assert_eq!(names, vec!["bug", "enhancement", "documentation", "triage"]);
+        assert_eq!(
+            changes(&transport),
+            vec![
+                (
+                    Method::Patch,
+                    format!("{}/Enhancement", LABELS),
+                    json!({"new_name": "enhancement", "color": "a2eeef"}),
+                ),
+                (
+                    Method::Patch,
+                    format!("{}/documentation", LABELS),
+                    json!({
+                        "new_name": "documentation",
+                        "color": "0075ca",
+                        "description": "Improvements or additions to documentation",
+                    }),
+                ),
+                (
+                    Method::Post,
+                    LABELS.to_string(),
+                    json!({"name": "triage", "color": "ededed"}),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_labels_leaves_matching_labels_alone() {
+        let transport = github(existing());
+        let installation = testing::installation(&transport);
+        let wanted = vec![
+            NewLabel::new("bug", "d73a4a"),
+            NewLabel::new("Enhancement", "A2EEEF"),
+        ];
+        testing::repo()
+            .sync_labels(&installation, &wanted, false)
+            .unwrap();
+        assert!(changes(&transport).is_empty());
+    }
+
+    #[test]
+    fn sync_labels_prunes() {
+        let transport = github(existing());
+        let installation = testing::installation(&transport);
+        testing::repo()
+            .sync_labels(&installation, &wanted(), true)
+            .unwrap();
+        assert_eq!(
+            changes(&transport).pop().unwrap(),
+            (Method::Delete, format!("{}/wontfix", LABELS), Value::Null)
+        );
+        assert_eq!(changes(&transport).len(), 4);
+    }
+}
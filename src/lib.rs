@@ -1,3 +1,6 @@
+// failure_derive generates its impls inside an anonymous const
+#![allow(non_local_definitions)]
+
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
@@ -11,21 +14,59 @@ mod error;
 mod json_web_token;
 
 mod app;
+mod config;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+mod pagination;
+mod rate_limit;
+#[cfg(test)]
+mod testing;
+pub mod transport;
 
 // Github types
 mod account;
+pub mod branch;
+pub mod checks;
+mod comment;
+mod commit;
+pub mod contents;
+pub mod git;
 mod installation;
+pub mod issue;
+mod label;
+mod milestone;
 pub mod pull_request;
 mod repo;
+pub mod review;
+mod status;
+
+pub mod event;
+pub mod webhook;
 
-pub use app::{App, AppInstallation};
+pub use app::{App, AppBuilder, AppInstallation, InstallationToken, TokenRequest};
+pub use pagination::Paginated;
+pub use rate_limit::{RateLimit, RateLimitPolicy};
+pub use transport::{CurlTransport, Transport};
 
 pub use account::{Account, Team};
-pub use installation::{Installation, Permissions};
-pub use pull_request::{PullRequest, PullRequestState};
-pub use repo::{Repo, RepoResult};
+pub use branch::{Branch, BranchProtection};
+pub use checks::{CheckRun, CheckSuite};
+pub use comment::IssueComment;
+pub use commit::{Commit, CommitDetails, CommitParent, GitCommit, Signature};
+pub use contents::{Content, File};
+pub use git::{FileChange, GitRef};
+pub use installation::{Installation, InstallationRepository, Permissions};
+pub use issue::{Issue, IssueFilter, IssueState};
+pub use label::{Label, NewLabel};
+pub use milestone::{Milestone, MilestoneState, MilestoneUpdate, NewMilestone};
+pub use pull_request::{MergeMethod, MergeResult, PullRequest, PullRequestFile, PullRequestState};
+pub use repo::{License, Repo, RepoResult};
+pub use review::{NewReview, Review, ReviewComment, ReviewEvent};
+pub use status::CombinedStatus;
 
-pub use error::GithubError;
+pub use error::{FieldError, GithubError};
 pub use json_web_token::JsonWebToken;
+pub use event::Event;
+pub use webhook::{Delivery, Webhook};
 
-const USER_AGENT: &'static str = "Github App - Rust";
+const USER_AGENT: &str = "Github App - Rust";
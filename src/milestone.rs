@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::pagination::with_query;
+use crate::{Account, AppInstallation, Issue, Paginated, PullRequest, Repo};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MilestoneState {
+    Open,
+    Closed,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Milestone {
+    pub id: usize,
+    pub node_id: String,
+    pub number: usize,
+    pub url: String,
+    pub html_url: String,
+    pub labels_url: String,
+    pub state: MilestoneState,
+    pub title: String,
+    pub description: Option<String>,
+    pub creator: Option<Account>,
+    pub open_issues: usize,
+    pub closed_issues: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub due_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewMilestone {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<MilestoneState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_on: Option<DateTime<Utc>>,
+}
+
+/// Changes to make to a milestone; fields left as `None` are not touched.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MilestoneUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<MilestoneState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_on: Option<DateTime<Utc>>,
+}
+
+impl Repo {
+    /// Milestones in `state`, or all of them if that's `None`, soonest due
+    /// first.
+    pub fn milestones(
+        &self,
+        installation: &AppInstallation,
+        state: Option<MilestoneState>,
+    ) -> Result<Vec<Milestone>, Error> {
+        self.iter_milestones(installation, state).collect()
+    }
+
+    pub fn iter_milestones<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        state: Option<MilestoneState>,
+    ) -> Paginated<'a, Milestone> {
+        let state = match state {
+            Some(MilestoneState::Open) => "open",
+            Some(MilestoneState::Closed) => "closed",
+            None => "all",
+        };
+        let url = with_query(&self.milestones_api_url(installation), "state", state);
+        installation.paginate(with_query(&url, "sort", "due_on"))
+    }
+
+    pub fn milestone(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+    ) -> Result<Milestone, Error> {
+        installation.get_json(format!(
+            "{}/{}",
+            self.milestones_api_url(installation),
+            number
+        ))
+    }
+
+    /// The open milestone with the earliest due date, ignoring any without
+    /// one.
+    pub fn upcoming_milestone(
+        &self,
+        installation: &AppInstallation,
+    ) -> Result<Option<Milestone>, Error> {
+        Ok(self
+            .milestones(installation, Some(MilestoneState::Open))?
+            .into_iter()
+            .filter(|milestone| milestone.due_on.is_some())
+            .min_by_key(|milestone| milestone.due_on))
+    }
+
+    pub fn create_milestone(
+        &self,
+        installation: &AppInstallation,
+        milestone: &NewMilestone,
+    ) -> Result<Milestone, Error> {
+        installation.post_json(self.milestones_api_url(installation), milestone)
+    }
+
+    pub fn update_milestone(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        update: &MilestoneUpdate,
+    ) -> Result<Milestone, Error> {
+        installation.patch_json(
+            format!("{}/{}", self.milestones_api_url(installation), number),
+            update,
+        )
+    }
+
+    pub fn delete_milestone(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+    ) -> Result<(), Error> {
+        installation.delete(format!(
+            "{}/{}",
+            self.milestones_api_url(installation),
+            number
+        ))
+    }
+
+    /// Puts an issue or pull request in the milestone numbered `milestone`,
+    /// or takes it out of its milestone if that's `None`.
+    pub fn set_milestone(
+        &self,
+        installation: &AppInstallation,
+        number: usize,
+        milestone: Option<usize>,
+    ) -> Result<Issue, Error> {
+        installation.patch_json(
+            self.issues_api_url(installation, number.to_string()),
+            &serde_json::json!({ "milestone": milestone }),
+        )
+    }
+
+    fn milestones_api_url(&self, installation: &AppInstallation) -> String {
+        installation.api(format!("repos/{}/milestones", self.full_name))
+    }
+}
+
+impl PullRequest {
+    /// See `Repo::set_milestone`.
+    pub fn set_milestone(
+        &self,
+        installation: &AppInstallation,
+        milestone: Option<usize>,
+    ) -> Result<Issue, Error> {
+        self.base
+            .repo
+            .set_milestone(installation, self.number, milestone)
+    }
+}
@@ -0,0 +1,551 @@
+//! An async counterpart to `App` and `AppInstallation`, enabled with the
+//! `async` cargo feature. It shares configuration and model types with the
+//! blocking API; build a blocking `App` (or `AppBuilder`) and convert it.
+//!
+//! Requests are sent with reqwest directly: a `Transport` set with
+//! `AppBuilder::transport` only applies to the blocking API, since the trait
+//! is synchronous. Everything else configured on the builder carries over.
+//!
+//! This covers less than the blocking API. Installations, repositories, pull
+//! requests and statuses are wrapped, along with installation tokens; for
+//! anything else use the typed `*_json` helpers, `paginate` or
+//! `raw_request` with the endpoint's URL and the shared model types. List
+//! endpoints are always fetched in full, as there is no lazy `Paginated`
+//! counterpart.
+
+use core::fmt;
+use core::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use chrono::prelude::*;
+use failure::Error;
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::app::{parse_json, InstallationToken, TokenRequest};
+use crate::pagination::{next_link, with_query};
+use crate::pull_request::Status;
+use crate::rate_limit::{RateLimits, CORE_RESOURCE};
+use crate::transport::Response;
+use crate::{
+    GithubError, Installation, PullRequest, PullRequestState, RateLimit, Repo, RepoResult,
+};
+
+#[derive(Clone)]
+pub struct App {
+    app: crate::App,
+    client: reqwest::Client,
+    // Held while minting a JWT so concurrent tasks don't all sign one
+    json_web_token_lock: Arc<Mutex<()>>,
+}
+
+impl From<crate::App> for App {
+    fn from(app: crate::App) -> App {
+        App {
+            app,
+            client: reqwest::Client::new(),
+            json_web_token_lock: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.app)
+    }
+}
+
+impl App {
+    pub fn new<T: Into<String>>(private_key: Vec<u8>, app_id: T) -> Result<App, Error> {
+        Ok(crate::App::new(private_key, app_id)?.into())
+    }
+
+    pub fn from_private_key_file<T: Into<String>>(path: &PathBuf, app_id: T) -> Result<App, Error> {
+        Ok(crate::App::from_private_key_file(path, app_id)?.into())
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.app.rate_limit()
+    }
+
+    pub fn rate_limit_for<T: AsRef<str>>(&self, resource: T) -> Option<RateLimit> {
+        self.app.rate_limit_for(resource)
+    }
+
+    pub async fn installations(&self) -> Result<Vec<AppInstallation>, Error> {
+        let mut installations = vec![];
+        let mut next = Some(with_query(
+            &self.app.api("app/installations"),
+            "per_page",
+            &self.app.per_page.to_string(),
+        ));
+        while let Some(url) = next {
+            let response = self.jwt_request(Method::GET, &url, None).await?;
+            next = response.header("Link").and_then(next_link);
+            let page: Vec<Installation> = serde_json::from_slice(&response.body)?;
+            installations.extend(
+                page.into_iter()
+                    .map(|ins| AppInstallation::new(self.clone(), ins)),
+            );
+        }
+        Ok(installations)
+    }
+
+    pub async fn installation(&self, installation_id: usize) -> Result<AppInstallation, Error> {
+        let url = self
+            .app
+            .api(format!("app/installations/{}", installation_id));
+        let response = self.jwt_request(Method::GET, &url, None).await?;
+        let installation: Installation = serde_json::from_slice(&response.body)?;
+        Ok(AppInstallation::new(self.clone(), installation))
+    }
+
+    async fn json_web_token(&self) -> Result<String, Error> {
+        let json_web_token = &self.app.json_web_token;
+        if !json_web_token.is_expired() {
+            return json_web_token.token();
+        }
+        let _guard = self.json_web_token_lock.lock().await;
+        // Another task may have renewed it while we waited
+        if !json_web_token.is_expired() {
+            return json_web_token.token();
+        }
+        // RSA signing is slow enough to stall the executor, so do it elsewhere
+        let json_web_token = json_web_token.clone();
+        tokio::task::spawn_blocking(move || json_web_token.token()).await?
+    }
+
+    async fn jwt_request(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response, Error> {
+        let authorization = format!("Bearer {}", self.json_web_token().await?);
+        self.execute(
+            &self.app.rate_limit,
+            method,
+            url,
+            authorization,
+            vec![],
+            body,
+        )
+        .await
+    }
+
+    /// The async twin of the blocking `App::execute`: records rate limit
+    /// headers and retries according to the configured `RateLimitPolicy`.
+    /// Unlike it, this bypasses the app's `Transport` and uses `client`.
+    async fn execute(
+        &self,
+        rate_limit: &RwLock<RateLimits>,
+        method: Method,
+        url: &str,
+        authorization: String,
+        headers: Vec<(String, String)>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response, Error> {
+        let mut defaults = vec![
+            (AUTHORIZATION.to_string(), authorization),
+            (USER_AGENT.to_string(), crate::USER_AGENT.into()),
+            (
+                ACCEPT.to_string(),
+                "application/vnd.github.machine-man-preview+json".into(),
+            ),
+        ];
+        if body.is_some() {
+            defaults.push((CONTENT_TYPE.to_string(), "application/json".into()));
+        }
+        defaults.retain(|(key, _)| !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)));
+        defaults.extend(headers);
+        let policy = &self.app.rate_limit_policy;
+        let resource = self.app.rate_limit_resource(url);
+        let mut attempt = 0;
+        loop {
+            let delay = policy.delay_before(rate_limit.read().unwrap().get(resource));
+            if let Some(delay) = delay {
+                info!("Rate limit exhausted, sleeping {:?} until it resets", delay);
+                tokio::time::sleep(delay).await;
+            }
+            debug!("About to {} {}", method, url);
+            let mut request = self.client.request(method.clone(), url);
+            for (key, value) in &defaults {
+                request = request.header(key.as_str(), value.as_str());
+            }
+            if let Some(ref body) = body {
+                request = request.body(body.clone());
+            }
+            let response = request.send().await?;
+            let response = Response {
+                status: u32::from(response.status().as_u16()),
+                headers: response
+                    .headers()
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            key.to_string(),
+                            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                        )
+                    })
+                    .collect(),
+                body: response.bytes().await?.to_vec(),
+            };
+            if let Some(limit) = RateLimit::from_response(&response) {
+                trace!("Rate limit: {:?}", limit);
+                rate_limit.write().unwrap().record(limit);
+            }
+            match GithubError::check(response) {
+                Ok(response) => return Ok(response),
+                Err(e) => match policy.retry_delay(&e, attempt) {
+                    Some(delay) => {
+                        info!("Rate limited ({}), retrying in {:?}", e, delay);
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    None => return Err(e.into()),
+                },
+            }
+        }
+    }
+}
+
+pub struct AppInstallation {
+    app: App,
+    // An async mutex rather than an RwLock so that only one task refreshes
+    // an expired token while the others wait for it
+    installation_token: Mutex<Option<InstallationToken>>,
+    token_request: Option<TokenRequest>,
+    rate_limit: RwLock<RateLimits>,
+    installation: Installation,
+}
+
+impl AppInstallation {
+    fn new(app: App, installation: Installation) -> AppInstallation {
+        AppInstallation {
+            app,
+            installation_token: Mutex::new(None),
+            token_request: None,
+            rate_limit: RwLock::new(RateLimits::default()),
+            installation,
+        }
+    }
+
+    async fn installation_token(&self) -> Result<String, Error> {
+        let mut installation_token = self.installation_token.lock().await;
+        if let Some(ref token) = *installation_token {
+            if token.is_fresh(self.app.app.token_refresh_margin) {
+                return Ok(token.token.clone());
+            }
+            debug!("Token expires at {}, refreshing it", token.expires_at);
+        }
+        info!("Renewing App Installation token for {}", self.id);
+        let token = match self.token_request {
+            Some(ref request) => self.create_token(request).await?,
+            None => {
+                let response = self
+                    .app
+                    .jwt_request(Method::POST, &self.access_tokens_api_url(), None)
+                    .await?;
+                serde_json::from_slice(&response.body)?
+            }
+        };
+        trace!("Updated App Installation token for {}", self.id);
+        let value = token.token.clone();
+        *installation_token = Some(token);
+        Ok(value)
+    }
+
+    /// Mints a new token restricted by `request`, separate from the one this
+    /// `AppInstallation` uses for its own requests.
+    pub async fn create_token(&self, request: &TokenRequest) -> Result<InstallationToken, Error> {
+        let body = serde_json::to_vec(request)?;
+        let response = self
+            .app
+            .jwt_request(Method::POST, &self.access_tokens_api_url(), Some(body))
+            .await?;
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// A copy of this installation whose requests all use tokens restricted
+    /// by `request`.
+    pub fn scoped(&self, request: TokenRequest) -> AppInstallation {
+        AppInstallation {
+            token_request: Some(request),
+            ..AppInstallation::new(self.app.clone(), self.installation.clone())
+        }
+    }
+
+    /// The token this installation is currently using, including what it
+    /// was actually granted. `None` until the first request mints one.
+    pub async fn current_token(&self) -> Option<InstallationToken> {
+        self.installation_token.lock().await.clone()
+    }
+
+    /// Revokes the token this installation is using, if any. A new one is
+    /// minted for the next request.
+    pub async fn revoke(&self) -> Result<(), Error> {
+        let mut installation_token = self.installation_token.lock().await;
+        match installation_token.take() {
+            Some(ref token) if token.expires_at > Utc::now() => {
+                info!("Revoking App Installation token for {}", self.id);
+                self.app
+                    .execute(
+                        &self.rate_limit,
+                        Method::DELETE,
+                        &self.api("installation/token"),
+                        format!("token {}", token.token),
+                        vec![],
+                        None,
+                    )
+                    .await?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn access_tokens_api_url(&self) -> String {
+        self.api(format!("app/installations/{}/access_tokens", self.id))
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit_for(CORE_RESOURCE)
+    }
+
+    pub fn rate_limit_for<T: AsRef<str>>(&self, resource: T) -> Option<RateLimit> {
+        self.rate_limit
+            .read()
+            .unwrap()
+            .get(resource.as_ref())
+            .cloned()
+    }
+
+    pub async fn repos(&self) -> Result<Vec<Repo>, Error> {
+        self.paginate_with(self.api("installation/repositories"), |body| {
+            Ok(serde_json::from_slice::<RepoResult>(body)?.repositories)
+        })
+        .await
+    }
+
+    /// pull_request_path should be of the form: :owner/:repo/pulls/:number
+    pub async fn pull_request<T: AsRef<str>>(
+        &self,
+        pull_request_path: T,
+    ) -> Result<PullRequest, Error> {
+        self.get_json(self.api(format!("repos/{}", pull_request_path.as_ref())))
+            .await
+    }
+
+    pub async fn pull_requests(
+        &self,
+        repo: &Repo,
+        state: Option<PullRequestState>,
+    ) -> Result<Vec<PullRequest>, Error> {
+        let state = match state {
+            Some(PullRequestState::Open) => "open",
+            Some(PullRequestState::Closed) => "closed",
+            None => "all",
+        };
+        let url = self.api(format!("repos/{}/pulls?state={}", repo.full_name, state));
+        self.paginate_with(url, |body| {
+            Ok(serde_json::from_slice::<Vec<PullRequest>>(body)?
+                .into_iter()
+                .map(|mut pr| {
+                    pr.installation_id = Some(self.id);
+                    pr
+                })
+                .collect())
+        })
+        .await
+    }
+
+    pub async fn statuses(&self, pull_request: &PullRequest) -> Result<Vec<Status>, Error> {
+        self.paginate_with(self.statuses_url(pull_request), |body| {
+            Ok(serde_json::from_slice(body)?)
+        })
+        .await
+    }
+
+    pub async fn set_status(
+        &self,
+        pull_request: &PullRequest,
+        status: &Status,
+    ) -> Result<(), Error> {
+        let body = serde_json::to_vec(status)?;
+        self.request(Method::POST, &self.statuses_url(pull_request), Some(body))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_json<T: AsRef<str>, R: DeserializeOwned>(&self, url: T) -> Result<R, Error> {
+        let response = self.request(Method::GET, url.as_ref(), None).await?;
+        parse_json(&response.body)
+    }
+
+    pub async fn post_json<T, B, R>(&self, url: T, body: &B) -> Result<R, Error>
+    where
+        T: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)?;
+        let response = self.request(Method::POST, url.as_ref(), Some(body)).await?;
+        parse_json(&response.body)
+    }
+
+    pub async fn patch_json<T, B, R>(&self, url: T, body: &B) -> Result<R, Error>
+    where
+        T: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)?;
+        let response = self
+            .request(Method::PATCH, url.as_ref(), Some(body))
+            .await?;
+        parse_json(&response.body)
+    }
+
+    pub async fn put_json<T, B, R>(&self, url: T, body: &B) -> Result<R, Error>
+    where
+        T: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)?;
+        let response = self.request(Method::PUT, url.as_ref(), Some(body)).await?;
+        parse_json(&response.body)
+    }
+
+    pub async fn delete<T: AsRef<str>>(&self, url: T) -> Result<(), Error> {
+        self.request(Method::DELETE, url.as_ref(), None).await?;
+        Ok(())
+    }
+
+    /// For the handful of DELETE endpoints that take a JSON body.
+    pub async fn delete_json<T, B, R>(&self, url: T, body: &B) -> Result<R, Error>
+    where
+        T: AsRef<str>,
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)?;
+        let response = self
+            .request(Method::DELETE, url.as_ref(), Some(body))
+            .await?;
+        parse_json(&response.body)
+    }
+
+    /// Sends an arbitrary request authenticated as this installation, for
+    /// endpoints this module doesn't wrap. `url` may be a full URL or a path
+    /// relative to the API base URL. `headers` are added to, or override,
+    /// the defaults. Error statuses are still turned into `GithubError`s.
+    pub async fn raw_request<T: AsRef<str>>(
+        &self,
+        method: crate::transport::Method,
+        url: T,
+        headers: Vec<(String, String)>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response, Error> {
+        let url = url.as_ref();
+        let url = if url.starts_with("http://") || url.starts_with("https://") {
+            url.to_string()
+        } else {
+            self.api(url)
+        };
+        let method = match method {
+            crate::transport::Method::Get => Method::GET,
+            crate::transport::Method::Post => Method::POST,
+            crate::transport::Method::Put => Method::PUT,
+            crate::transport::Method::Patch => Method::PATCH,
+            crate::transport::Method::Delete => Method::DELETE,
+        };
+        self.request_with_headers(method, &url, headers, body).await
+    }
+
+    /// Fetches every page of a list endpoint that returns a JSON array.
+    /// Unlike the blocking `AppInstallation::paginate`, this isn't lazy.
+    pub async fn paginate<T, U>(&self, url: U) -> Result<Vec<T>, Error>
+    where
+        T: DeserializeOwned,
+        U: Into<String>,
+    {
+        self.paginate_with(url.into(), |body| Ok(serde_json::from_slice(body)?))
+            .await
+    }
+
+    /// Joins `path` onto the configured REST API base URL.
+    pub fn api<T: AsRef<str>>(&self, path: T) -> String {
+        self.app.app.api(path)
+    }
+
+    fn statuses_url(&self, pull_request: &PullRequest) -> String {
+        self.api(format!(
+            "repos/{}/statuses/{}",
+            pull_request.base.repo.full_name, pull_request.head.sha
+        ))
+    }
+
+    /// Fetches every page of a list endpoint.
+    async fn paginate_with<T, F>(&self, url: String, parse: F) -> Result<Vec<T>, Error>
+    where
+        F: Fn(&[u8]) -> Result<Vec<T>, Error>,
+    {
+        let mut items = vec![];
+        let mut next = Some(with_query(
+            &url,
+            "per_page",
+            &self.app.app.per_page.to_string(),
+        ));
+        while let Some(url) = next {
+            let response = self.request(Method::GET, &url, None).await?;
+            next = response.header("Link").and_then(next_link);
+            items.extend(parse(&response.body)?);
+        }
+        Ok(items)
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response, Error> {
+        self.request_with_headers(method, url, vec![], body).await
+    }
+
+    async fn request_with_headers(
+        &self,
+        method: Method,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response, Error> {
+        let authorization = format!("token {}", self.installation_token().await?);
+        self.app
+            .execute(&self.rate_limit, method, url, authorization, headers, body)
+            .await
+    }
+}
+
+impl Deref for AppInstallation {
+    type Target = Installation;
+
+    fn deref(&self) -> &Installation {
+        &self.installation
+    }
+}
+
+impl fmt::Debug for AppInstallation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.installation)
+    }
+}
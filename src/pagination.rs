@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use failure::Error;
+
+use crate::transport::Response;
+
+type Fetch<'a> = Box<dyn Fn(&str) -> Result<Response, Error> + 'a>;
+type Parse<'a, T> = Box<dyn Fn(&[u8]) -> Result<Vec<T>, Error> + 'a>;
+
+/// A lazy iterator over a GitHub list endpoint.
+///
+/// Pages are only requested as the iterator is advanced, and further pages
+/// are found by following the `Link: <...>; rel="next"` response header.
+/// Iteration stops after the first error is yielded.
+pub struct Paginated<'a, T> {
+    fetch: Fetch<'a>,
+    parse: Parse<'a, T>,
+    next_url: Option<String>,
+    per_page: usize,
+    started: bool,
+    items: VecDeque<T>,
+}
+
+impl<'a, T> Paginated<'a, T> {
+    pub(crate) fn new<U, F, P>(url: U, per_page: usize, fetch: F, parse: P) -> Paginated<'a, T>
+    where
+        U: Into<String>,
+        F: Fn(&str) -> Result<Response, Error> + 'a,
+        P: Fn(&[u8]) -> Result<Vec<T>, Error> + 'a,
+    {
+        Paginated {
+            fetch: Box::new(fetch),
+            parse: Box::new(parse),
+            next_url: Some(url.into()),
+            per_page,
+            started: false,
+            items: VecDeque::new(),
+        }
+    }
+
+    /// Overrides the page size. Only has an effect before the first page is
+    /// fetched; subsequent pages reuse whatever GitHub put in the `Link` header.
+    pub fn per_page(mut self, per_page: usize) -> Paginated<'a, T> {
+        self.per_page = per_page;
+        self
+    }
+
+    /// Fetches every remaining page and collects the results.
+    pub fn collect_all(self) -> Result<Vec<T>, Error> {
+        self.collect()
+    }
+
+    fn fetch_page(&mut self, url: String) -> Result<(), Error> {
+        let url = if self.started {
+            url
+        } else {
+            self.started = true;
+            with_query(&url, "per_page", &self.per_page.to_string())
+        };
+        let response = (self.fetch)(&url)?;
+        trace!("Fetched page {} ({})", url, response.status);
+        self.next_url = response.header("Link").and_then(next_link);
+        self.items.extend((self.parse)(&response.body)?);
+        Ok(())
+    }
+}
+
+impl<'a, T> Iterator for Paginated<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        loop {
+            if let Some(item) = self.items.pop_front() {
+                return Some(Ok(item));
+            }
+            let url = self.next_url.take()?;
+            if let Err(e) = self.fetch_page(url) {
+                self.next_url = None;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Appends `key=value` to the query string of `url`.
+pub(crate) fn with_query(url: &str, key: &str, value: &str) -> String {
+    let separator = if !url.contains('?') {
+        "?"
+    } else if url.ends_with('?') || url.ends_with('&') {
+        ""
+    } else {
+        "&"
+    };
+    format!("{}{}{}={}", url, separator, key, value)
+}
+
+/// Extracts the `rel="next"` target from a `Link` header.
+pub(crate) fn next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let mut sections = part.split(';');
+        let url = sections.next()?.trim();
+        let is_next = sections.any(|param| {
+            let param = param.trim();
+            param == "rel=\"next\"" || param == "rel=next"
+        });
+        if is_next && url.starts_with('<') && url.ends_with('>') {
+            Some(url[1..url.len() - 1].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Percent-encodes `value` for use as a single path segment or query value.
+pub(crate) fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn with_query_picks_separator() {
+        assert_eq!(with_query("https://x/a", "page", "2"), "https://x/a?page=2");
+        assert_eq!(
+            with_query("https://x/a?state=all", "page", "2"),
+            "https://x/a?state=all&page=2"
+        );
+        assert_eq!(
+            with_query("https://x/a?", "page", "2"),
+            "https://x/a?page=2"
+        );
+        assert_eq!(
+            with_query("https://x/a?state=all&", "page", "2"),
+            "https://x/a?state=all&page=2"
+        );
+    }
+
+    #[test]
+    fn next_link_finds_next() {
+        let link = r#"<https://api.github.com/repositories/1/issues?page=2>; rel="next", <https://api.github.com/repositories/1/issues?page=5>; rel="last""#;
+        assert_eq!(
+            next_link(link).as_deref(),
+            Some("https://api.github.com/repositories/1/issues?page=2")
+        );
+        // Order and spacing vary
+        let link = r#"<https://x/?page=1>; rel="prev",<https://x/?page=3>;rel=next"#;
+        assert_eq!(next_link(link).as_deref(), Some("https://x/?page=3"));
+    }
+
+    #[test]
+    fn next_link_without_next() {
+        let link = r#"<https://x/?page=1>; rel="first", <https://x/?page=4>; rel="prev""#;
+        assert_eq!(next_link(link), None);
+        assert_eq!(next_link(""), None);
+        assert_eq!(next_link(r#"https://x/?page=2; rel="next""#), None);
+    }
+
+    #[test]
+    fn encode_escapes_reserved_characters() {
+        assert_eq!(encode("feature/a b"), "feature%2Fa%20b");
+        assert_eq!(encode("v1.0-rc_1~"), "v1.0-rc_1~");
+        assert_eq!(encode("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn paginated_follows_link_headers() {
+        let transport = testing::github(|request| match request.url.as_str() {
+            "https://api.github.com/items?per_page=2" => testing::response(
+                200,
+                &[(
+                    "Link",
+                    r#"<https://api.github.com/items?page=2>; rel="next""#,
+                )],
+                "[1, 2]",
+            ),
+            "https://api.github.com/items?page=2" => testing::response(200, &[], "[3]"),
+            url => panic!("unexpected request for {}", url),
+        });
+        let installation = testing::installation(&transport);
+        let mut items = installation
+            .paginate::<u32, _>(installation.api("items"))
+            .per_page(2);
+        assert_eq!(items.next().unwrap().unwrap(), 1);
+        // Only the first page has been fetched so far
+        assert_eq!(transport.requests().len(), 2);
+        let rest: Vec<u32> = items.collect::<Result<_, _>>().unwrap();
+        assert_eq!(rest, vec![2, 3]);
+        assert_eq!(transport.requests().len(), 3);
+    }
+
+    #[test]
+    fn paginated_stops_after_an_error() {
+        let transport = testing::github(|_| {
+            testing::response(
+                500,
+                &[(
+                    "Link",
+                    r#"<https://api.github.com/items?page=2>; rel="next""#,
+                )],
+                "oops",
+            )
+        });
+        let installation = testing::installation(&transport);
+        let mut items = installation.paginate::<u32, _>(installation.api("items"));
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
+    }
+}
@@ -1,16 +1,25 @@
 use failure::Error;
 
-use crate::{Account, AppInstallation, Repo, Team};
+use crate::transport::Method;
+use crate::{
+    Account, AppInstallation, Commit, GithubError, Label, Milestone, Paginated, Repo, Team,
+};
 use chrono::prelude::*;
+use serde::{Deserialize, Deserializer};
 
 fn default_context() -> String {
     "default".to_string()
 }
 
+/// GitHub sends `null` for a status without a target URL or description.
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct PullRequest {
     pub user: Account,
-    pub head: Ref,
+    pub head: HeadRef,
     pub base: Ref,
     pub url: String,
     pub id: usize,
@@ -23,18 +32,18 @@ pub struct PullRequest {
     pub state: PullRequestState,
     pub locked: bool,
     pub title: String,
-    pub body: String,
+    pub body: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub closed_at: Option<DateTime<Utc>>,
     pub merged_at: Option<DateTime<Utc>>,
-    pub merge_commit_sha: String,
+    pub merge_commit_sha: Option<String>,
     pub assignee: Option<Account>,
     pub assignees: Vec<Account>,
     pub requested_reviewers: Vec<Account>,
     pub requested_teams: Vec<Team>,
-    pub labels: Vec<String>,
-    pub milestone: Option<String>,
+    pub labels: Vec<Label>,
+    pub milestone: Option<Milestone>,
     pub commits_url: String,
     pub review_comments_url: String,
     pub review_comment_url: String,
@@ -44,7 +53,7 @@ pub struct PullRequest {
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
-#[serde(rename_all="lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum State {
     Error,
     Failure,
@@ -52,31 +61,306 @@ pub enum State {
     Success,
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MergeResult {
+    pub sha: String,
+    pub merged: bool,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct MergeRequest<'a> {
+    merge_method: MergeMethod,
+    sha: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_message: Option<&'a str>,
+}
+
+#[derive(Default, Serialize)]
+struct EditRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<PullRequestState>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Added,
+    Removed,
+    Modified,
+    Renamed,
+    Copied,
+    Changed,
+    Unchanged,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PullRequestFile {
+    pub sha: Option<String>,
+    pub filename: String,
+    pub status: FileStatus,
+    pub additions: usize,
+    pub deletions: usize,
+    pub changes: usize,
+    pub blob_url: String,
+    pub raw_url: String,
+    pub contents_url: String,
+    /// Missing for binary files and very large diffs.
+    pub patch: Option<String>,
+    /// The old name of a renamed file.
+    pub previous_filename: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
 pub struct Status {
     pub state: State,
+    #[serde(deserialize_with = "null_as_empty")]
     pub target_url: String,
+    #[serde(deserialize_with = "null_as_empty")]
     pub description: String,
-    #[serde(default="default_context")]
+    #[serde(default = "default_context")]
     pub context: String,
+    /// Set by GitHub; ignored when setting a status.
+    #[serde(default, skip_serializing)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing)]
+    pub creator: Option<Account>,
+}
+
+impl Status {
+    /// A status to set, without a target URL or description.
+    pub fn new<T: Into<String>>(state: State, context: T) -> Status {
+        Status {
+            state,
+            target_url: String::new(),
+            description: String::new(),
+            context: context.into(),
+            created_at: None,
+            creator: None,
+        }
+    }
+
+    /// Where the status links to, such as a build log.
+    pub fn target_url<T: Into<String>>(mut self, target_url: T) -> Self {
+        self.target_url = target_url.into();
+        self
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = description.into();
+        self
+    }
 }
 
 impl PullRequest {
     pub fn statuses(&self, installation: &AppInstallation) -> Result<Vec<Status>, Error> {
-        Ok(serde_json::from_slice(&installation.get(&self.statuses_url)?)?)
+        self.iter_statuses(installation).collect()
     }
 
-    pub fn last_status_for_context<T: AsRef<str>>(&self, installation: &AppInstallation, context: T) -> Result<Option<Status>, Error> {
+    pub fn iter_statuses<'a>(&self, installation: &'a AppInstallation) -> Paginated<'a, Status> {
+        self.base.repo.iter_statuses(installation, &self.head.sha)
+    }
+
+    /// The current status for `context` on the head commit, if any.
+    pub fn last_status_for_context<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        context: T,
+    ) -> Result<Option<Status>, Error> {
         let context = context.as_ref();
-        Ok(self.statuses(&installation)?.into_iter().filter(|c| c.context == context).next())
+        let combined = self
+            .base
+            .repo
+            .combined_status(installation, &self.head.sha)?;
+        Ok(combined.statuses.into_iter().find(|c| c.context == context))
     }
 
     pub fn set_status(&self, installation: &AppInstallation, status: &Status) -> Result<(), Error> {
-        let json = serde_json::to_string(status)?;
-        let body = installation.post(&self.statuses_url, Some(json.as_bytes()))?;
+        self.base
+            .repo
+            .set_status(installation, &self.head.sha, status)?;
+        Ok(())
+    }
+
+    /// Merges the pull request. The merge only goes ahead if the head is
+    /// still at `expected_head_sha`, or at `self.head.sha` if that's `None`.
+    pub fn merge(
+        &self,
+        installation: &AppInstallation,
+        method: MergeMethod,
+        commit_title: Option<&str>,
+        commit_message: Option<&str>,
+        expected_head_sha: Option<&str>,
+    ) -> Result<MergeResult, Error> {
+        let request = MergeRequest {
+            merge_method: method,
+            sha: expected_head_sha.unwrap_or(&self.head.sha),
+            commit_title,
+            commit_message,
+        };
+        installation
+            .put_json(format!("{}/merge", self.api_url(installation)), &request)
+            .map_err(merge_error)
+    }
+
+    /// Merges the base branch into the head branch. GitHub does this in the
+    /// background, so the branch may not have moved yet when this returns.
+    pub fn update_branch(&self, installation: &AppInstallation) -> Result<(), Error> {
+        let request = serde_json::json!({ "expected_head_sha": self.head.sha });
+        let _: serde_json::Value = installation
+            .put_json(
+                format!("{}/update-branch", self.api_url(installation)),
+                &request,
+            )
+            .map_err(merge_error)?;
         Ok(())
     }
+
+    pub fn close(&self, installation: &AppInstallation) -> Result<PullRequest, Error> {
+        self.update(
+            installation,
+            &EditRequest {
+                state: Some(PullRequestState::Closed),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn reopen(&self, installation: &AppInstallation) -> Result<PullRequest, Error> {
+        self.update(
+            installation,
+            &EditRequest {
+                state: Some(PullRequestState::Open),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Changes the title, body or base branch; `None` leaves a field as is.
+    pub fn edit(
+        &self,
+        installation: &AppInstallation,
+        title: Option<&str>,
+        body: Option<&str>,
+        base: Option<&str>,
+    ) -> Result<PullRequest, Error> {
+        self.update(
+            installation,
+            &EditRequest {
+                title,
+                body,
+                base,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn update(
+        &self,
+        installation: &AppInstallation,
+        request: &EditRequest,
+    ) -> Result<PullRequest, Error> {
+        let mut pull_request: PullRequest =
+            installation.patch_json(self.api_url(installation), request)?;
+        pull_request.installation_id = Some(installation.id);
+        Ok(pull_request)
+    }
+
+    /// The files changed, up to GitHub's limit of 3000.
+    pub fn files(&self, installation: &AppInstallation) -> Result<Vec<PullRequestFile>, Error> {
+        self.iter_files(installation).collect()
+    }
+
+    pub fn iter_files<'a>(
+        &self,
+        installation: &'a AppInstallation,
+    ) -> Paginated<'a, PullRequestFile> {
+        installation.paginate(format!("{}/files", self.api_url(installation)))
+    }
+
+    /// The commits on the pull request, oldest first, up to GitHub's limit
+    /// of 250.
+    pub fn commits(&self, installation: &AppInstallation) -> Result<Vec<Commit>, Error> {
+        self.iter_commits(installation).collect()
+    }
+
+    pub fn iter_commits<'a>(&self, installation: &'a AppInstallation) -> Paginated<'a, Commit> {
+        installation.paginate(format!("{}/commits", self.api_url(installation)))
+    }
+
+    /// The whole pull request as a unified diff. Invalid UTF-8 in the diff
+    /// is replaced.
+    pub fn diff(&self, installation: &AppInstallation) -> Result<String, Error> {
+        self.raw(installation, "application/vnd.github.v3.diff")
+    }
+
+    /// The pull request as a series of patches, one per commit, in
+    /// `git format-patch` form.
+    pub fn patch(&self, installation: &AppInstallation) -> Result<String, Error> {
+        self.raw(installation, "application/vnd.github.v3.patch")
+    }
+
+    fn raw(&self, installation: &AppInstallation, media_type: &str) -> Result<String, Error> {
+        let response = installation.request_with_headers(
+            Method::Get,
+            self.api_url(installation),
+            vec![("Accept".to_string(), media_type.to_string())],
+            None,
+        )?;
+        Ok(String::from_utf8_lossy(&response.body).into_owned())
+    }
+
+    pub(crate) fn api_url(&self, installation: &AppInstallation) -> String {
+        installation.api(format!(
+            "repos/{}/pulls/{}",
+            self.base.repo.full_name, self.number
+        ))
+    }
+}
+/// GitHub answers merge requests with 405 when the pull request can't be
+/// merged and 409 when its head has moved on.
+fn merge_error(error: Error) -> Error {
+    match error.downcast::<GithubError>() {
+        Ok(GithubError::HttpError {
+            status: 405,
+            message,
+            ..
+        }) => GithubError::NotMergeable { message }.into(),
+        Ok(GithubError::HttpError {
+            status: 409,
+            message,
+            ..
+        }) => GithubError::HeadShaMismatch { message }.into(),
+        // update-branch reports a moved head as a validation failure
+        Ok(GithubError::ValidationFailed { ref message, .. })
+            if message.contains("expected head sha") =>
+        {
+            GithubError::HeadShaMismatch {
+                message: message.clone(),
+            }
+            .into()
+        }
+        Ok(error) => error.into(),
+        Err(error) => error,
+    }
 }
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum PullRequestState {
     #[serde(rename = "open")]
@@ -94,3 +378,83 @@ pub struct Ref {
     pub user: Account,
     pub repo: Repo,
 }
+
+/// The branch a pull request merges from. Unlike the base, its repository
+/// is `None` once the fork it came from has been deleted.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HeadRef {
+    pub label: String,
+    #[serde(rename = "ref")]
+    pub pr_ref: String,
+    pub sha: String,
+    pub user: Account,
+    pub repo: Option<Repo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: u32, message: &str) -> Error {
+        GithubError::HttpError {
+            status,
+            message: message.into(),
+            documentation_url: None,
+        }
+        .into()
+    }
+
+    fn validation_failed(message: &str) -> Error {
+        GithubError::ValidationFailed {
+            message: message.into(),
+            errors: vec![],
+            documentation_url: None,
+        }
+        .into()
+    }
+
+    #[test]
+    fn merge_error_not_mergeable() {
+        let error = merge_error(http_error(405, "Pull Request is not mergeable"));
+        match error.downcast::<GithubError>() {
+            Ok(GithubError::NotMergeable { message }) => {
+                assert_eq!(message, "Pull Request is not mergeable")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn merge_error_head_moved() {
+        let error = merge_error(http_error(409, "Head branch was modified."));
+        match error.downcast::<GithubError>() {
+            Ok(GithubError::HeadShaMismatch { message }) => {
+                assert_eq!(message, "Head branch was modified.")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let error = merge_error(validation_failed(
+            "expected head sha didn't match current head ref.",
+        ));
+        match error.downcast::<GithubError>() {
+            Ok(GithubError::HeadShaMismatch { .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn merge_error_passes_others_through() {
+        match merge_error(validation_failed("Validation Failed")).downcast::<GithubError>() {
+            Ok(GithubError::ValidationFailed { message, .. }) => {
+                assert_eq!(message, "Validation Failed")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match merge_error(http_error(404, "Not Found")).downcast::<GithubError>() {
+            Ok(GithubError::HttpError { status: 404, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        let error = merge_error(format_err!("connection reset"));
+        assert!(error.downcast::<GithubError>().is_err());
+    }
+}
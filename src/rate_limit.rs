@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::prelude::*;
+
+use crate::transport::Response;
+use crate::GithubError;
+
+/// The resource REST requests count against unless GitHub says otherwise.
+pub(crate) const CORE_RESOURCE: &str = "core";
+
+/// The rate limit state GitHub reported on the most recent response for a
+/// resource.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    pub used: Option<u32>,
+    pub reset: DateTime<Utc>,
+    pub resource: Option<String>,
+}
+
+impl RateLimit {
+    pub(crate) fn from_response(response: &Response) -> Option<RateLimit> {
+        let number = |name| response.header(name).and_then(|v| v.parse::<u32>().ok());
+        let reset = response
+            .header("X-RateLimit-Reset")
+            .and_then(|v| v.parse::<i64>().ok())
+            .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())?;
+        Some(RateLimit {
+            limit: number("X-RateLimit-Limit")?,
+            remaining: number("X-RateLimit-Remaining")?,
+            used: number("X-RateLimit-Used"),
+            reset,
+            resource: response.header("X-RateLimit-Resource").map(String::from),
+        })
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == 0 && self.reset > Utc::now()
+    }
+}
+
+/// The latest `RateLimit` for each resource (`core`, `search`, `graphql`
+/// and so on), which GitHub counts separately.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RateLimits {
+    limits: HashMap<String, RateLimit>,
+}
+
+impl RateLimits {
+    pub(crate) fn get(&self, resource: &str) -> Option<&RateLimit> {
+        self.limits.get(resource)
+    }
+
+    pub(crate) fn record(&mut self, limit: RateLimit) {
+        let resource = limit
+            .resource
+            .clone()
+            .unwrap_or_else(|| CORE_RESOURCE.to_string());
+        self.limits.insert(resource, limit);
+    }
+}
+
+/// Which resource a request to `url` will count against, so that an
+/// exhausted `search` limit doesn't hold up `core` requests.
+pub(crate) fn resource_for(api_url: &str, graphql_url: &str, url: &str) -> &'static str {
+    let url = url.split('?').next().unwrap_or(url);
+    if url == graphql_url {
+        return "graphql";
+    }
+    let path = url.strip_prefix(api_url).unwrap_or(url);
+    if path.starts_with("/search/code") {
+        "code_search"
+    } else if path.starts_with("/search/") {
+        "search"
+    } else {
+        CORE_RESOURCE
+    }
+}
+
+/// What to do when GitHub says we've been rate limited.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RateLimitPolicy {
+    /// Return `GithubError::RateLimited` straight away.
+    #[default]
+    Fail,
+    /// Sleep until the limit resets (or for as long as `Retry-After` asks),
+    /// backing off exponentially from `initial_backoff` when GitHub gives no
+    /// hint, as it does for some secondary rate limits. Gives up once a single
+    /// wait would exceed `max_wait` or after `max_retries` attempts.
+    Wait {
+        max_retries: u32,
+        initial_backoff: Duration,
+        max_wait: Duration,
+    },
+}
+
+impl RateLimitPolicy {
+    /// A `Wait` policy using GitHub's recommendation of backing off for at
+    /// least a minute on secondary rate limits.
+    pub fn wait() -> RateLimitPolicy {
+        RateLimitPolicy::Wait {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(60),
+            max_wait: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// How long to sleep before sending a request, given the last known
+    /// limit, or `None` to send it straight away.
+    pub(crate) fn delay_before(&self, last: Option<&RateLimit>) -> Option<Duration> {
+        match (self, last) {
+            (RateLimitPolicy::Wait { max_wait, .. }, Some(limit)) if limit.is_exhausted() => {
+                until(limit.reset).filter(|delay| delay <= max_wait)
+            }
+            _ => None,
+        }
+    }
+
+    /// How long to sleep before retrying a rate limited request, or `None`
+    /// to give up and return the error.
+    pub(crate) fn retry_delay(&self, error: &GithubError, attempt: u32) -> Option<Duration> {
+        let (max_retries, initial_backoff, max_wait) = match self {
+            RateLimitPolicy::Fail => return None,
+            RateLimitPolicy::Wait {
+                max_retries,
+                initial_backoff,
+                max_wait,
+            } => (*max_retries, *initial_backoff, *max_wait),
+        };
+        let reset = match error {
+            GithubError::RateLimited { reset, .. } => reset,
+            _ => return None,
+        };
+        if attempt >= max_retries {
+            return None;
+        }
+        let delay = match reset {
+            Some(reset) => until(*reset).unwrap_or_else(|| Duration::from_secs(1)),
+            None => initial_backoff * 2u32.saturating_pow(attempt),
+        };
+        if delay > max_wait {
+            None
+        } else {
+            Some(delay)
+        }
+    }
+}
+
+fn until(time: DateTime<Utc>) -> Option<Duration> {
+    (time - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API: &str = "https://api.github.com";
+    const GRAPHQL: &str = "https://api.github.com/graphql";
+
+    fn limit(resource: Option<&str>, remaining: u32) -> RateLimit {
+        RateLimit {
+            limit: 30,
+            remaining,
+            used: None,
+            reset: Utc::now() + chrono::Duration::minutes(1),
+            resource: resource.map(String::from),
+        }
+    }
+
+    fn wait() -> RateLimitPolicy {
+        RateLimitPolicy::Wait {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(10),
+            max_wait: Duration::from_secs(120),
+        }
+    }
+
+    fn rate_limited(reset: Option<DateTime<Utc>>) -> GithubError {
+        GithubError::RateLimited {
+            message: "API rate limit exceeded".into(),
+            reset,
+        }
+    }
+
+    #[test]
+    fn fail_never_waits() {
+        let policy = RateLimitPolicy::Fail;
+        assert_eq!(policy.delay_before(Some(&limit(None, 0))), None);
+        assert_eq!(policy.retry_delay(&rate_limited(None), 0), None);
+    }
+
+    #[test]
+    fn delay_before_waits_for_exhausted_limit() {
+        let delay = wait().delay_before(Some(&limit(None, 0))).unwrap();
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+        assert_eq!(wait().delay_before(Some(&limit(None, 1))), None);
+        assert_eq!(wait().delay_before(None), None);
+    }
+
+    #[test]
+    fn delay_before_ignores_past_and_distant_resets() {
+        let mut past = limit(None, 0);
+        past.reset = Utc::now() - chrono::Duration::seconds(5);
+        assert_eq!(wait().delay_before(Some(&past)), None);
+        let mut distant = limit(None, 0);
+        distant.reset = Utc::now() + chrono::Duration::minutes(30);
+        assert_eq!(wait().delay_before(Some(&distant)), None);
+    }
+
+    #[test]
+    fn retry_delay_waits_until_reset() {
+        let reset = Utc::now() + chrono::Duration::seconds(30);
+        let delay = wait().retry_delay(&rate_limited(Some(reset)), 0).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+        // A reset that has already passed retries almost straight away
+        let reset = Utc::now() - chrono::Duration::seconds(30);
+        assert_eq!(
+            wait().retry_delay(&rate_limited(Some(reset)), 0),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn retry_delay_backs_off_without_reset() {
+        let policy = wait();
+        let error = rate_limited(None);
+        assert_eq!(policy.retry_delay(&error, 0), Some(Duration::from_secs(10)));
+        assert_eq!(policy.retry_delay(&error, 1), Some(Duration::from_secs(20)));
+        assert_eq!(policy.retry_delay(&error, 2), Some(Duration::from_secs(40)));
+        assert_eq!(policy.retry_delay(&error, 3), None);
+    }
+
+    #[test]
+    fn retry_delay_gives_up() {
+        let policy = RateLimitPolicy::Wait {
+            max_retries: 10,
+            initial_backoff: Duration::from_secs(60),
+            max_wait: Duration::from_secs(120),
+        };
+        let error = rate_limited(None);
+        assert_eq!(
+            policy.retry_delay(&error, 1),
+            Some(Duration::from_secs(120))
+        );
+        // The next backoff would exceed max_wait
+        assert_eq!(policy.retry_delay(&error, 2), None);
+        let reset = Utc::now() + chrono::Duration::minutes(10);
+        assert_eq!(policy.retry_delay(&rate_limited(Some(reset)), 0), None);
+        // Only rate limits are retried
+        let error = GithubError::ServerError {
+            status: 502,
+            message: "Bad gateway".into(),
+        };
+        assert_eq!(policy.retry_delay(&error, 0), None);
+    }
+
+    #[test]
+    fn resource_for_url() {
+        let resource = |url| resource_for(API, GRAPHQL, url);
+        assert_eq!(
+            resource("https://api.github.com/search/issues?q=is:pr"),
+            "search"
+        );
+        assert_eq!(
+            resource("https://api.github.com/search/code?q=fn"),
+            "code_search"
+        );
+        assert_eq!(resource(GRAPHQL), "graphql");
+        assert_eq!(
+            resource("https://api.github.com/repos/o/search/pulls"),
+            "core"
+        );
+        assert_eq!(resource("https://api.github.com/repos/o/graphql"), "core");
+        assert_eq!(
+            resource("https://api.github.com/installation/repositories"),
+            "core"
+        );
+    }
+
+    #[test]
+    fn limits_are_kept_per_resource() {
+        let mut limits = RateLimits::default();
+        limits.record(limit(Some("core"), 4000));
+        limits.record(limit(Some("search"), 0));
+        assert_eq!(limits.get("core").unwrap().remaining, 4000);
+        assert_eq!(limits.get("search").unwrap().remaining, 0);
+
+        // Responses without X-RateLimit-Resource count as core
+        limits.record(limit(None, 3999));
+        assert_eq!(limits.get("core").unwrap().remaining, 3999);
+        assert!(limits.get("graphql").is_none());
+
+        let policy = RateLimitPolicy::wait();
+        assert!(policy.delay_before(limits.get("search")).is_some());
+        assert!(policy.delay_before(limits.get("core")).is_none());
+    }
+}
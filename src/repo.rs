@@ -1,7 +1,8 @@
 use crate::app::AppInstallation;
 
-use crate::{Account, PullRequest, PullRequestState};
+use crate::{Account, Paginated, PullRequest, PullRequestState};
 use chrono::prelude::*;
+use serde::de::{self, Deserialize, Deserializer};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Repo {
@@ -52,8 +53,10 @@ pub struct Repo {
     pub labels_url: String,
     pub releases_url: String,
     pub deployments_url: String,
+    #[serde(deserialize_with = "timestamp")]
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(deserialize_with = "timestamp")]
     pub pushed_at: DateTime<Utc>,
     pub git_url: String,
     pub ssh_url: String,
@@ -73,7 +76,7 @@ pub struct Repo {
     pub mirror_url: Option<String>,
     pub archived: bool,
     pub open_issues_count: usize,
-    pub license: Option<String>,
+    pub license: Option<License>,
     pub forks: usize,
     pub open_issues: usize,
     pub watchers: usize,
@@ -87,7 +90,15 @@ impl Repo {
         installation: &AppInstallation,
         state: Option<PullRequestState>,
     ) -> Result<Vec<PullRequest>, failure::Error> {
-        let mut url = format!("{}?", self.pulls_url.replace("{/number}", ""));
+        self.iter_pull_requests(installation, state).collect()
+    }
+
+    pub fn iter_pull_requests<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        state: Option<PullRequestState>,
+    ) -> Paginated<'a, PullRequest> {
+        let mut url = installation.api(format!("repos/{}/pulls?", self.full_name));
         if let Some(state) = state {
             match state {
                 PullRequestState::Open => url = format!("{}&state=open", url),
@@ -96,16 +107,46 @@ impl Repo {
         } else {
             url = format!("{}&state=all", url)
         };
-        Ok(
-            serde_json::from_slice::<Vec<PullRequest>>(&installation.get(url)?)?
+        installation.paginate_with(url, move |body| {
+            Ok(serde_json::from_slice::<Vec<PullRequest>>(body)?
                 .into_iter()
                 .map(|mut pr| {
                     pr.installation_id = Some(installation.id);
                     pr
                 })
-                .collect(),
-        )
+                .collect())
+        })
+    }
+}
+
+/// Push webhooks send `created_at` and `pushed_at` as Unix timestamps rather
+/// than the ISO 8601 strings the REST API uses.
+fn timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Unix(i64),
+        Iso(DateTime<Utc>),
     }
+    match Timestamp::deserialize(deserializer)? {
+        Timestamp::Iso(time) => Ok(time),
+        Timestamp::Unix(epoch) => Utc
+            .timestamp_opt(epoch, 0)
+            .single()
+            .ok_or_else(|| de::Error::custom(format!("invalid timestamp: {}", epoch))),
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct License {
+    pub key: String,
+    pub name: String,
+    pub spdx_id: Option<String>,
+    pub url: Option<String>,
+    pub node_id: String,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
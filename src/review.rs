@@ -0,0 +1,248 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::{Account, AppInstallation, Paginated, PullRequest};
+
+// Webhook payloads spell review states in lowercase, the REST API in capitals
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ReviewState {
+    #[serde(rename = "APPROVED", alias = "approved")]
+    Approved,
+    #[serde(rename = "CHANGES_REQUESTED", alias = "changes_requested")]
+    ChangesRequested,
+    #[serde(rename = "COMMENTED", alias = "commented")]
+    Commented,
+    #[serde(rename = "DISMISSED", alias = "dismissed")]
+    Dismissed,
+    #[serde(rename = "PENDING", alias = "pending")]
+    Pending,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Review {
+    pub id: usize,
+    pub node_id: String,
+    pub user: Option<Account>,
+    pub body: Option<String>,
+    pub state: ReviewState,
+    pub html_url: String,
+    pub pull_request_url: String,
+    pub commit_id: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub author_association: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewEvent {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+/// Which side of the diff a review comment is anchored to: `Left` for
+/// deleted lines, `Right` for added or unchanged ones.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A review to submit. Leaving `event` unset creates a pending review that
+/// has to be submitted from the web UI.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NewReview {
+    /// Defaults to the pull request's current head when submitted, so that
+    /// inline comments line up with the diff they were written against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<ReviewEvent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<DraftReviewComment>,
+}
+
+/// An inline comment submitted as part of a `NewReview`.
+///
+/// `line` is a line number in the file, not a position in the diff. Set
+/// `start_line` as well to comment on a range.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DraftReviewComment {
+    pub path: String,
+    pub body: String,
+    pub line: usize,
+    pub side: Side,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_side: Option<Side>,
+}
+
+impl DraftReviewComment {
+    /// A comment on a single line of the new version of `path`.
+    pub fn new<P: Into<String>, B: Into<String>>(
+        path: P,
+        line: usize,
+        body: B,
+    ) -> DraftReviewComment {
+        DraftReviewComment {
+            path: path.into(),
+            body: body.into(),
+            line,
+            side: Side::Right,
+            start_line: None,
+            start_side: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ReviewComment {
+    pub id: usize,
+    pub node_id: String,
+    pub pull_request_review_id: Option<usize>,
+    pub in_reply_to_id: Option<usize>,
+    pub diff_hunk: String,
+    pub path: String,
+    pub position: Option<usize>,
+    pub original_position: Option<usize>,
+    pub line: Option<usize>,
+    pub original_line: Option<usize>,
+    pub start_line: Option<usize>,
+    pub side: Option<Side>,
+    pub commit_id: String,
+    pub original_commit_id: String,
+    pub user: Option<Account>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub html_url: String,
+    pub pull_request_url: String,
+    pub author_association: String,
+}
+
+#[derive(Serialize)]
+struct ReviewRequest<'a> {
+    reviewers: Vec<&'a str>,
+    team_reviewers: Vec<&'a str>,
+}
+
+impl PullRequest {
+    pub fn reviews(&self, installation: &AppInstallation) -> Result<Vec<Review>, Error> {
+        self.iter_reviews(installation).collect()
+    }
+
+    pub fn iter_reviews<'a>(&self, installation: &'a AppInstallation) -> Paginated<'a, Review> {
+        installation.paginate(format!("{}/reviews", self.api_url(installation)))
+    }
+
+    /// Submits a review, along with any inline comments, in one go.
+    pub fn submit_review(
+        &self,
+        installation: &AppInstallation,
+        review: &NewReview,
+    ) -> Result<Review, Error> {
+        let mut review = review.clone();
+        if review.commit_id.is_none() {
+            review.commit_id = Some(self.head.sha.clone());
+        }
+        installation.post_json(format!("{}/reviews", self.api_url(installation)), &review)
+    }
+
+    pub fn dismiss_review<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        review_id: usize,
+        message: T,
+    ) -> Result<Review, Error> {
+        installation.put_json(
+            format!(
+                "{}/reviews/{}/dismissals",
+                self.api_url(installation),
+                review_id
+            ),
+            &serde_json::json!({ "message": message.as_ref(), "event": "DISMISS" }),
+        )
+    }
+
+    /// Requests reviews from users, by login, and from teams, by slug.
+    pub fn request_reviewers<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        reviewers: &[T1],
+        team_reviewers: &[T2],
+    ) -> Result<PullRequest, Error> {
+        let mut pull_request: PullRequest = installation.post_json(
+            format!("{}/requested_reviewers", self.api_url(installation)),
+            &ReviewRequest::new(reviewers, team_reviewers),
+        )?;
+        pull_request.installation_id = Some(installation.id);
+        Ok(pull_request)
+    }
+
+    pub fn remove_requested_reviewers<T1: AsRef<str>, T2: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        reviewers: &[T1],
+        team_reviewers: &[T2],
+    ) -> Result<PullRequest, Error> {
+        let mut pull_request: PullRequest = installation.delete_json(
+            format!("{}/requested_reviewers", self.api_url(installation)),
+            &ReviewRequest::new(reviewers, team_reviewers),
+        )?;
+        pull_request.installation_id = Some(installation.id);
+        Ok(pull_request)
+    }
+
+    /// All inline comments on the diff, from every review.
+    pub fn review_comments(
+        &self,
+        installation: &AppInstallation,
+    ) -> Result<Vec<ReviewComment>, Error> {
+        self.iter_review_comments(installation).collect()
+    }
+
+    pub fn iter_review_comments<'a>(
+        &self,
+        installation: &'a AppInstallation,
+    ) -> Paginated<'a, ReviewComment> {
+        installation.paginate(format!("{}/comments", self.api_url(installation)))
+    }
+
+    /// The inline comments belonging to a single review.
+    pub fn comments_for_review(
+        &self,
+        installation: &AppInstallation,
+        review_id: usize,
+    ) -> Result<Vec<ReviewComment>, Error> {
+        self.iter_comments_for_review(installation, review_id)
+            .collect()
+    }
+
+    pub fn iter_comments_for_review<'a>(
+        &self,
+        installation: &'a AppInstallation,
+        review_id: usize,
+    ) -> Paginated<'a, ReviewComment> {
+        installation.paginate(format!(
+            "{}/reviews/{}/comments",
+            self.api_url(installation),
+            review_id
+        ))
+    }
+}
+
+impl<'a> ReviewRequest<'a> {
+    fn new<T1: AsRef<str>, T2: AsRef<str>>(
+        reviewers: &'a [T1],
+        team_reviewers: &'a [T2],
+    ) -> ReviewRequest<'a> {
+        ReviewRequest {
+            reviewers: reviewers.iter().map(AsRef::as_ref).collect(),
+            team_reviewers: team_reviewers.iter().map(AsRef::as_ref).collect(),
+        }
+    }
+}
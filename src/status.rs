@@ -0,0 +1,79 @@
+use failure::Error;
+
+use crate::pagination::{encode, with_query};
+use crate::pull_request::{State, Status};
+use crate::{AppInstallation, Paginated, Repo};
+
+/// The overall state of a commit: `Failure` if any context failed or
+/// errored, `Pending` if any has yet to report, and `Success` otherwise.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CombinedStatus {
+    pub state: State,
+    pub sha: String,
+    pub total_count: usize,
+    /// The latest status for each context.
+    pub statuses: Vec<Status>,
+    pub commit_url: String,
+    pub url: String,
+}
+
+impl Repo {
+    /// Every status set on `git_ref`, which may be a SHA, branch or tag,
+    /// newest first.
+    pub fn statuses<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<Vec<Status>, Error> {
+        self.iter_statuses(installation, git_ref).collect()
+    }
+
+    pub fn iter_statuses<'a, T: AsRef<str>>(
+        &self,
+        installation: &'a AppInstallation,
+        git_ref: T,
+    ) -> Paginated<'a, Status> {
+        installation.paginate(self.commit_url(installation, git_ref.as_ref(), "statuses"))
+    }
+
+    /// Only the latest status for each context, along with their aggregate
+    /// state. Covers up to 100 contexts.
+    pub fn combined_status<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        git_ref: T,
+    ) -> Result<CombinedStatus, Error> {
+        installation.get_json(with_query(
+            &self.commit_url(installation, git_ref.as_ref(), "status"),
+            "per_page",
+            "100",
+        ))
+    }
+
+    /// Sets a status on the commit `sha`. Statuses can only be set on
+    /// commits, not branches or tags.
+    pub fn set_status<T: AsRef<str>>(
+        &self,
+        installation: &AppInstallation,
+        sha: T,
+        status: &Status,
+    ) -> Result<Status, Error> {
+        installation.post_json(
+            installation.api(format!(
+                "repos/{}/statuses/{}",
+                self.full_name,
+                encode(sha.as_ref())
+            )),
+            status,
+        )
+    }
+
+    fn commit_url(&self, installation: &AppInstallation, git_ref: &str, path: &str) -> String {
+        installation.api(format!(
+            "repos/{}/commits/{}/{}",
+            self.full_name,
+            encode(git_ref),
+            path
+        ))
+    }
+}
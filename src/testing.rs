@@ -0,0 +1,137 @@
+//! Helpers for unit tests that talk to a fake GitHub.
+
+use core::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use failure::Error;
+
+use crate::transport::{Request, Response, Transport};
+use crate::{App, AppInstallation, Installation, JsonWebToken, Repo};
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// A `Transport` that answers every request with `handler` and records
+/// what was sent. Clones share their recorded requests.
+#[derive(Clone)]
+pub(crate) struct MockTransport {
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockTransport {
+    pub(crate) fn new<F>(handler: F) -> MockTransport
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        MockTransport {
+            handler: Arc::new(handler),
+            requests: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub(crate) fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MockTransport")
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        self.requests.lock().unwrap().push(request.clone());
+        Ok((self.handler)(request))
+    }
+}
+
+/// A fake GitHub for an installation: token mints are answered with
+/// hour-long tokens and everything else goes to `handler`.
+pub(crate) fn github<F>(handler: F) -> MockTransport
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    github_with_token_ttl(chrono::Duration::hours(1), handler)
+}
+
+/// Like `github`, with tokens that expire after `ttl`. The tokens are
+/// numbered in the order they are minted: `t1`, `t2`, ...
+pub(crate) fn github_with_token_ttl<F>(ttl: chrono::Duration, handler: F) -> MockTransport
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let minted = Mutex::new(0);
+    MockTransport::new(move |request| {
+        if request.url.ends_with("/access_tokens") {
+            // Give concurrent requests a chance to pile up
+            thread::sleep(Duration::from_millis(20));
+            let mut minted = minted.lock().unwrap();
+            *minted += 1;
+            return access_token(&format!("t{}", minted), ttl);
+        }
+        handler(request)
+    })
+}
+
+/// How many installation tokens have been minted through `transport`.
+pub(crate) fn mints(transport: &MockTransport) -> usize {
+    transport
+        .requests()
+        .iter()
+        .filter(|request| request.url.ends_with("/access_tokens"))
+        .count()
+}
+
+pub(crate) fn response(status: u32, headers: &[(&str, &str)], body: &str) -> Response {
+    Response {
+        status,
+        headers: headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+pub(crate) fn app(transport: &MockTransport) -> App {
+    let json_web_token = JsonWebToken::new(
+        include_bytes!("../tests/fixtures/test-private-key.pem").to_vec(),
+        "31016",
+    )
+    .unwrap();
+    App::builder(json_web_token)
+        .transport(transport.clone())
+        .build()
+}
+
+pub(crate) fn installation(transport: &MockTransport) -> AppInstallation {
+    let event: serde_json::Value =
+        serde_json::from_slice(include_bytes!("../tests/fixtures/events/installation.json"))
+            .unwrap();
+    let installation: Installation = serde_json::from_value(event["installation"].clone()).unwrap();
+    AppInstallation::new(app(transport), installation)
+}
+
+/// A freshly minted installation token, as `POST .../access_tokens` returns.
+pub(crate) fn access_token(token: &str, expires_in: chrono::Duration) -> Response {
+    let body = serde_json::json!({
+        "token": token,
+        "expires_at": chrono::Utc::now() + expires_in,
+        "permissions": {"checks": "write", "contents": "read", "metadata": "read"},
+        "repository_selection": "selected",
+    });
+    response(201, &[], &body.to_string())
+}
+
+/// `Codertocat/Hello-World`, as webhooks and the REST API describe it.
+pub(crate) fn repo() -> Repo {
+    let event: serde_json::Value = serde_json::from_slice(include_bytes!(
+        "../tests/fixtures/events/issue_comment.json"
+    ))
+    .unwrap();
+    serde_json::from_value(event["repository"].clone()).unwrap()
+}
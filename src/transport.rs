@@ -0,0 +1,188 @@
+use core::fmt;
+use std::sync::Mutex;
+
+use curl::easy::{Easy, List};
+use failure::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+/// A fully formed HTTP request, including authentication headers.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Looks up a response header, ignoring the case of its name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sends requests on behalf of an `App`.
+///
+/// Implementations should follow redirects and return any other response
+/// GitHub sends, whatever its status; error statuses, rate limits and
+/// retries are handled by the caller.
+/// Wrapping another `Transport` is a convenient way to add logging, metrics
+/// or canned responses in tests.
+pub trait Transport: fmt::Debug + Send + Sync {
+    fn send(&self, request: &Request) -> Result<Response, Error>;
+}
+
+const MAX_REDIRECTIONS: u32 = 5;
+
+/// Idle handles kept beyond this are dropped, closing their connections.
+const MAX_IDLE_HANDLES: usize = 16;
+
+/// The default `Transport`, backed by libcurl.
+///
+/// Curl handles are pooled and reused between requests so that their
+/// connections are kept alive rather than doing a fresh TCP and TLS handshake
+/// every time. Requests made from several threads at once each take their own
+/// handle.
+#[derive(Debug, Default)]
+pub struct CurlTransport {
+    handles: Mutex<Vec<Easy>>,
+}
+
+impl CurlTransport {
+    pub fn new() -> CurlTransport {
+        CurlTransport::default()
+    }
+}
+
+impl Transport for CurlTransport {
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        let handle = self.handles.lock().unwrap().pop();
+        let mut easy = match handle {
+            Some(mut easy) => {
+                trace!("Reusing pooled curl handle");
+                // reset clears options but keeps live connections
+                easy.reset();
+                easy
+            }
+            None => Easy::new(),
+        };
+        let response = easy_run(&mut easy, request)?;
+        let mut handles = self.handles.lock().unwrap();
+        if handles.len() < MAX_IDLE_HANDLES {
+            handles.push(easy);
+        }
+        Ok(response)
+    }
+}
+
+fn easy_run(easy: &mut Easy, request: &Request) -> Result<Response, Error> {
+    debug!("About to {:?} {}", request.method, request.url);
+    let mut data = Vec::with_capacity(8192);
+    let mut headers = Vec::new();
+    easy.url(&request.url)?;
+    // GitHub redirects requests for renamed and transferred repositories
+    easy.follow_location(true)?;
+    easy.max_redirections(MAX_REDIRECTIONS)?;
+
+    let mut list = List::new();
+    for (key, value) in &request.headers {
+        list.append(&format!("{}: {}", key, value))?;
+    }
+    easy.http_headers(list)?;
+    match request.method {
+        Method::Get => easy.get(true)?,
+        Method::Post => easy.post(true)?,
+        Method::Put => easy.custom_request("PUT")?,
+        Method::Patch => easy.custom_request("PATCH")?,
+        Method::Delete => easy.custom_request("DELETE")?,
+    }
+    match request.body {
+        Some(ref body) => easy.post_fields_copy(body)?,
+        // GitHub wants an explicit `Content-Length: 0` on empty PUTs, and
+        // this also stops curl reading a POST body from stdin
+        None if request.method != Method::Get => easy.post_fields_copy(&[])?,
+        None => (),
+    }
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|chunk| {
+            data.extend_from_slice(chunk);
+            Ok(chunk.len())
+        })?;
+        transfer.header_function(|header| {
+            record_header(&mut headers, header);
+            true
+        })?;
+        transfer.perform()?;
+    }
+    trace!("Got {:#?}", String::from_utf8_lossy(&data));
+    Ok(Response {
+        status: easy.response_code()?,
+        headers,
+        body: data,
+    })
+}
+
+/// Adds a raw header line to `headers`. When redirects are followed curl
+/// reports the headers of every hop, so each status line starts afresh and
+/// only the final response's headers are kept.
+fn record_header(headers: &mut Vec<(String, String)>, line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    if line.starts_with("HTTP/") {
+        headers.clear();
+    } else if let Some(idx) = line.find(':') {
+        let (key, value) = line.split_at(idx);
+        headers.push((key.trim().to_string(), value[1..].trim().to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_final_responses_headers_are_kept() {
+        let mut headers = vec![];
+        for line in &[
+            "HTTP/2 301 \r\n",
+            "location: https://api.github.com/repositories/1296269\r\n",
+            "x-ratelimit-remaining: 4999\r\n",
+            "\r\n",
+            "HTTP/2 200 \r\n",
+            "etag: \"644b5b0155e6404a9cc4bd9d8b1ae730\"\r\n",
+            "x-ratelimit-remaining: 4998\r\n",
+            "\r\n",
+        ] {
+            record_header(&mut headers, line.as_bytes());
+        }
+        let response = Response {
+            status: 200,
+            headers,
+            body: vec![],
+        };
+        assert_eq!(response.header("Location"), None);
+        assert_eq!(response.header("X-RateLimit-Remaining"), Some("4998"));
+        assert_eq!(
+            response.header("ETag"),
+            Some("\"644b5b0155e6404a9cc4bd9d8b1ae730\"")
+        );
+    }
+}
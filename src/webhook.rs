@@ -0,0 +1,268 @@
+use core::fmt;
+
+use failure::Error;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{Event, GithubError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+const EVENT_HEADER: &str = "X-GitHub-Event";
+const DELIVERY_HEADER: &str = "X-GitHub-Delivery";
+
+/// Verifies and parses incoming webhook deliveries.
+///
+/// This doesn't depend on any particular HTTP server: hand it the request
+/// headers as name/value pairs along with the raw, unparsed body.
+#[derive(Clone)]
+pub struct Webhook {
+    secret: Vec<u8>,
+}
+
+/// A verified webhook delivery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delivery {
+    /// The `X-GitHub-Delivery` GUID.
+    pub id: String,
+    /// The `X-GitHub-Event` name, e.g. `pull_request`.
+    pub name: String,
+    pub action: Option<String>,
+    pub event: Event,
+}
+
+#[derive(Deserialize)]
+struct Action {
+    action: Option<String>,
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Webhook {{ secret: <redacted> }}")
+    }
+}
+
+impl Webhook {
+    pub fn new<T: Into<Vec<u8>>>(secret: T) -> Webhook {
+        Webhook {
+            secret: secret.into(),
+        }
+    }
+
+    /// Checks an `X-Hub-Signature-256` header value (`sha256=<hex digest>`)
+    /// against the HMAC of `body`, in constant time.
+    pub fn verify<T: AsRef<str>>(&self, signature: T, body: &[u8]) -> Result<(), Error> {
+        let signature = signature.as_ref().trim();
+        let digest = signature
+            .strip_prefix("sha256=")
+            .and_then(|digest| hex::decode(digest).ok())
+            .ok_or(GithubError::InvalidSignature)?;
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).map_err(|_| GithubError::InvalidSignature)?;
+        mac.update(body);
+        mac.verify_slice(&digest)
+            .map_err(|_| GithubError::InvalidSignature)?;
+        Ok(())
+    }
+
+    /// Verifies the delivery's signature and parses its headers and payload.
+    pub fn parse<I, K, V>(&self, headers: I, body: &[u8]) -> Result<Delivery, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let headers: Vec<(K, V)> = headers.into_iter().collect();
+        let header = |name: &str| -> Result<String, GithubError> {
+            headers
+                .iter()
+                .find(|(key, _)| key.as_ref().eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_ref().to_string())
+                .ok_or_else(|| GithubError::MissingHeader { name: name.into() })
+        };
+        self.verify(header(SIGNATURE_HEADER)?, body)?;
+        let id = header(DELIVERY_HEADER)?;
+        let name = header(EVENT_HEADER)?;
+        debug!("Verified {} webhook delivery {}", name, id);
+        let Action { action } = serde_json::from_slice(body)?;
+        let event = Event::from_slice(&name, body)?;
+        Ok(Delivery {
+            id,
+            name,
+            action,
+            event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from GitHub's webhook validation docs
+    const SECRET: &str = "It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    const SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    const PING: &[u8] = br#"{"zen": "Keep it logically awesome.", "hook_id": 1, "hook": {}}"#;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn is_invalid_signature(result: Result<(), Error>) -> bool {
+        match result {
+            Err(e) => matches!(
+                e.downcast::<GithubError>(),
+                Ok(GithubError::InvalidSignature)
+            ),
+            Ok(()) => false,
+        }
+    }
+
+    fn missing_header(result: Result<Delivery, Error>) -> String {
+        match result.unwrap_err().downcast::<GithubError>() {
+            Ok(GithubError::MissingHeader { name }) => name,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_accepts_known_signature() {
+        let webhook = Webhook::new(SECRET);
+        webhook.verify(SIGNATURE, BODY).unwrap();
+        webhook
+            .verify(SIGNATURE.to_uppercase().replace("SHA256=", "sha256="), BODY)
+            .unwrap();
+        webhook.verify(format!(" {}\r\n", SIGNATURE), BODY).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_tampered_body() {
+        let webhook = Webhook::new(SECRET);
+        assert!(is_invalid_signature(
+            webhook.verify(SIGNATURE, b"Hello, World?")
+        ));
+        assert!(is_invalid_signature(webhook.verify(SIGNATURE, b"")));
+    }
+
+    #[test]
+    fn verify_rejects_wrong_secret() {
+        let webhook = Webhook::new("It's a Secret to Nobody");
+        assert!(is_invalid_signature(webhook.verify(SIGNATURE, BODY)));
+        assert!(is_invalid_signature(
+            Webhook::new("").verify(SIGNATURE, BODY)
+        ));
+    }
+
+    #[test]
+    fn verify_rejects_missing_or_wrong_prefix() {
+        let webhook = Webhook::new(SECRET);
+        let digest = &SIGNATURE["sha256=".len()..];
+        assert!(is_invalid_signature(webhook.verify(digest, BODY)));
+        assert!(is_invalid_signature(
+            webhook.verify(format!("sha1={}", digest), BODY)
+        ));
+        assert!(is_invalid_signature(
+            webhook.verify(format!("SHA256={}", digest), BODY)
+        ));
+        assert!(is_invalid_signature(webhook.verify("", BODY)));
+    }
+
+    #[test]
+    fn verify_rejects_non_hex_digest() {
+        let webhook = Webhook::new(SECRET);
+        assert!(is_invalid_signature(webhook.verify("sha256=not-hex", BODY)));
+        // Odd length
+        assert!(is_invalid_signature(
+            webhook.verify(&SIGNATURE[..SIGNATURE.len() - 1], BODY)
+        ));
+        // Valid hex, but truncated
+        assert!(is_invalid_signature(
+            webhook.verify(&SIGNATURE[..SIGNATURE.len() - 2], BODY)
+        ));
+    }
+
+    #[test]
+    fn parse_ignores_header_case() {
+        let webhook = Webhook::new(SECRET);
+        let delivery = webhook
+            .parse(
+                vec![
+                    ("x-hub-signature-256", sign(SECRET, PING)),
+                    ("X-GITHUB-EVENT", "ping".to_string()),
+                    ("x-github-delivery", "72d3162e-cc78".to_string()),
+                ],
+                PING,
+            )
+            .unwrap();
+        assert_eq!(delivery.id, "72d3162e-cc78");
+        assert_eq!(delivery.name, "ping");
+        assert_eq!(delivery.action, None);
+        match delivery.event {
+            Event::Ping(ping) => assert_eq!(ping.hook_id, 1),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_keeps_deliveries_that_dont_fit_their_type() {
+        let webhook = Webhook::new(SECRET);
+        let body = br#"{"action": "opened", "pull_request": null}"#;
+        let delivery = webhook
+            .parse(
+                vec![
+                    (SIGNATURE_HEADER, sign(SECRET, body)),
+                    (EVENT_HEADER, "pull_request".to_string()),
+                    (DELIVERY_HEADER, "72d3162e-cc78".to_string()),
+                ],
+                body,
+            )
+            .unwrap();
+        assert_eq!(delivery.id, "72d3162e-cc78");
+        assert_eq!(delivery.name, "pull_request");
+        assert_eq!(delivery.action.as_deref(), Some("opened"));
+        match delivery.event {
+            Event::Unparsed { name, payload, .. } => {
+                assert_eq!(name, "pull_request");
+                assert_eq!(payload["action"], "opened");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_signature_before_parsing() {
+        let webhook = Webhook::new(SECRET);
+        let result = webhook.parse(
+            vec![
+                (SIGNATURE_HEADER, sign("wrong", PING)),
+                (EVENT_HEADER, "ping".to_string()),
+                (DELIVERY_HEADER, "1".to_string()),
+            ],
+            PING,
+        );
+        match result.unwrap_err().downcast::<GithubError>() {
+            Ok(GithubError::InvalidSignature) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_reports_each_missing_header() {
+        let webhook = Webhook::new(SECRET);
+        let headers = [
+            (SIGNATURE_HEADER, sign(SECRET, PING)),
+            (EVENT_HEADER, "ping".to_string()),
+            (DELIVERY_HEADER, "1".to_string()),
+        ];
+        for missing in &[SIGNATURE_HEADER, EVENT_HEADER, DELIVERY_HEADER] {
+            let headers = headers.iter().filter(|(name, _)| name != missing).cloned();
+            assert_eq!(missing_header(webhook.parse(headers, PING)), *missing);
+        }
+    }
+}